@@ -1,17 +1,106 @@
 use code_generator::{CodeGenerator, Address, OpCode};
-use parser::{ParseNode, Parser};
-use lexer::{TokenInfo, Tag};
+use parser::{ParseNode, Parser, ParseError};
+use lexer::{TokenInfo, Tag, Span};
+use diagnostics::undeclared;
+use ast::statement::{BasicType, DeclId};
+use cst::NodeKind;
 
 pub struct ExprAttributes {
     pub place  : Address,
+    pub basic_type : BasicType,
 }
 
 impl ExprAttributes {
     fn new(addr : Address) -> Self {
         ExprAttributes {
             place : addr,
+            basic_type : BasicType::Int,
         }
     }
+
+    fn float(addr : Address) -> Self {
+        ExprAttributes {
+            place : addr,
+            basic_type : BasicType::Float,
+        }
+    }
+}
+
+// Emit a binary arithmetic operation, converting the int operand to float
+// if the other one is a float.
+fn arith(code_gen : &mut CodeGenerator, op : OpCode, fop : OpCode,
+         a1 : ExprAttributes, a2 : ExprAttributes) -> ExprAttributes {
+    let tmp = code_gen.new_temp();
+    if a1.basic_type == BasicType::Float || a2.basic_type == BasicType::Float {
+        let x = to_float(code_gen, a1);
+        let y = to_float(code_gen, a2);
+        code_gen.emit(fop, tmp, x, y);
+        ExprAttributes::float(tmp)
+    } else {
+        code_gen.emit(op, tmp, a1.place, a2.place);
+        ExprAttributes::new(tmp)
+    }
+}
+
+pub fn to_float(code_gen : &mut CodeGenerator, a : ExprAttributes) -> Address {
+    if a.basic_type == BasicType::Float {
+        a.place
+    } else {
+        let tmp = code_gen.new_temp();
+        code_gen.emit(OpCode::IntToFloat, tmp, a.place, a.place);
+        tmp
+    }
+}
+
+// Arguments are converted to the types of the parameters, all of them
+// evaluated before they are passed.
+pub fn generate_call(code_gen : &mut CodeGenerator, name : &str, args : &[BoolExpr]) -> ExprAttributes {
+    let (params, ret) = code_gen.function_type(name);
+    let mut places = Vec::new();
+    for (a, t) in args.iter().zip(params) {
+        let attr = a.generate_code(code_gen);
+        places.push(match t {
+            BasicType::Float => to_float(code_gen, attr),
+            _ => to_int(code_gen, attr),
+        });
+    }
+    ExprAttributes {
+        place : code_gen.emit_call(name, places),
+        basic_type : ret.unwrap_or(BasicType::Int),
+    }
+}
+
+// ID ( args ), in an expression or a statement.
+pub fn parse_call(parser : &mut Parser) -> Result<(String, Vec<BoolExpr>), ParseError> {
+    parser.start_node(NodeKind::Call);
+    let name = match parser.match_lookahead(Tag::Ide)?.info {
+        TokenInfo::Ide(s) => s,
+        _ => unreachable!("Wrong token info inside identifier."),
+    };
+    parser.match_lookahead(Tag::LParen)?;
+    let mut args = Vec::new();
+    if parser.lookahead.tag != Tag::RParen {
+        loop {
+            args.push(*BoolExpr::parse(parser)?);
+            if parser.lookahead.tag != Tag::Comma {
+                break;
+            }
+            parser.shift_lookahead();
+        }
+    }
+    parser.match_lookahead(Tag::RParen)?;
+    parser.finish_node();
+    Ok((name, args))
+}
+
+pub fn to_int(code_gen : &mut CodeGenerator, a : ExprAttributes) -> Address {
+    if a.basic_type == BasicType::Float {
+        let tmp = code_gen.new_temp();
+        code_gen.emit(OpCode::FloatToInt, tmp, a.place, a.place);
+        tmp
+    } else {
+        a.place
+    }
 }
 
 #[derive(PartialEq, Debug)]
@@ -20,8 +109,21 @@ pub enum Relop {
     Leq, Les,
 }
 
+#[derive(Debug)]
+pub struct BoolExpr {
+    pub kind : BoolExprKind,
+    pub span : Span,
+}
+
+// Spans are not part of the structural equality of the tree.
+impl PartialEq for BoolExpr {
+    fn eq(&self, other : &BoolExpr) -> bool {
+        self.kind == other.kind
+    }
+}
+
 #[derive(PartialEq, Debug)]
-pub enum BoolExpr {
+pub enum BoolExprKind {
     Or(Box<BoolExpr>, Box<BoolExpr>),
     And(Box<BoolExpr>, Box<BoolExpr>),
     Eq(Box<BoolExpr>, Box<BoolExpr>),
@@ -31,42 +133,48 @@ pub enum BoolExpr {
 }
 
 impl BoolExpr {
+    pub fn new(kind : BoolExprKind, span : Span) -> Box<Self> {
+        Box::new(BoolExpr {
+            kind,
+            span,
+        })
+    }
+
     pub fn generate_code(&self, code_gen : &mut CodeGenerator) -> ExprAttributes {
-        match self {
-            &BoolExpr::Or(ref e1, ref e2) => {
+        match self.kind {
+            BoolExprKind::Or(ref e1, ref e2) => {
                 let a1 = e1.generate_code(code_gen);
                 let a2 = e2.generate_code(code_gen);
                 let tmp = code_gen.new_temp();
                 code_gen.emit(OpCode::Or, tmp, a1.place, a2.place);
                 ExprAttributes::new(tmp)
             },
-            &BoolExpr::And(ref e1, ref e2) => {
+            BoolExprKind::And(ref e1, ref e2) => {
                 let a1 = e1.generate_code(code_gen);
                 let a2 = e2.generate_code(code_gen);
                 let tmp = code_gen.new_temp();
                 code_gen.emit(OpCode::And, tmp, a1.place, a2.place);
                 ExprAttributes::new(tmp)
             },
-            &BoolExpr::Eq(ref e1, ref e2) => {
+            BoolExprKind::Eq(ref e1, ref e2) => {
                 let a1 = e1.generate_code(code_gen);
                 let a2 = e2.generate_code(code_gen);
-                let tmp = code_gen.new_temp();
-                code_gen.emit(OpCode::Sub, tmp, a1.place, a2.place);
+                let tmp = arith(code_gen, OpCode::Sub, OpCode::FSub, a1, a2).place;
                 code_gen.emit(OpCode::Not, tmp, tmp, tmp);
                 ExprAttributes::new(tmp)
             },
-            &BoolExpr::Neq(ref e1, ref e2) => {
+            BoolExprKind::Neq(ref e1, ref e2) => {
                 let a1 = e1.generate_code(code_gen);
                 let a2 = e2.generate_code(code_gen);
-                let tmp = code_gen.new_temp();
-                code_gen.emit(OpCode::Sub, tmp, a1.place, a2.place);
+                let tmp = arith(code_gen, OpCode::Sub, OpCode::FSub, a1, a2).place;
+                code_gen.emit(OpCode::Not, tmp, tmp, tmp);
+                code_gen.emit(OpCode::Not, tmp, tmp, tmp);
                 ExprAttributes::new(tmp)
             },
-            &BoolExpr::Relop(ref rel, ref e1, ref e2) => {
+            BoolExprKind::Relop(ref rel, ref e1, ref e2) => {
                 let a1 = e1.generate_code(code_gen);
                 let a2 = e2.generate_code(code_gen);
-                let tmp = code_gen.new_temp();
-                code_gen.emit(OpCode::Sub, tmp, a1.place, a2.place);
+                let tmp = arith(code_gen, OpCode::Sub, OpCode::FSub, a1, a2).place;
                 match rel {
                     &Relop::Ge  => {
                         code_gen.emit(OpCode::IsNeg, tmp, tmp, tmp);
@@ -85,7 +193,7 @@ impl BoolExpr {
                 }
                 ExprAttributes::new(tmp)                
             },
-            &BoolExpr::NumExpr(ref e1) => {
+            BoolExprKind::NumExpr(ref e1) => {
                 e1.generate_code(code_gen)
             },                        
         }
@@ -93,77 +201,105 @@ impl BoolExpr {
 }
 
 impl ParseNode for BoolExpr {
-    fn parse(parser : &mut Parser) -> Box<Self> {
+    fn parse(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        let start = parser.lookahead.span;
+        let cp = parser.checkpoint();
         // bool -> join
-        let mut x = BoolExpr::join(parser);
+        let mut x = BoolExpr::join(parser)?;
         while parser.lookahead.info == TokenInfo::Or {
             // bool -> join || bool
+            parser.start_node_at(cp, NodeKind::BinaryExpr);
             parser.shift_lookahead();
-            x = Box::new(BoolExpr::Or(x, BoolExpr::join(parser)));
+            let y = BoolExpr::join(parser)?;
+            parser.finish_node();
+            x = BoolExpr::new(BoolExprKind::Or(x, y), parser.span_from(start));
         }
-        x
+        Ok(x)
     } 
 }
 
 impl BoolExpr {
-    fn join(parser : &mut Parser) -> Box<Self> {
+    fn join(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        let start = parser.lookahead.span;
+        let cp = parser.checkpoint();
         // join -> equ
-        let mut x = BoolExpr::equality(parser);
+        let mut x = BoolExpr::equality(parser)?;
         while parser.lookahead.info == TokenInfo::And {
             // join -> join && equality
+            parser.start_node_at(cp, NodeKind::BinaryExpr);
             parser.shift_lookahead();
-            x = Box::new(BoolExpr::And(x, BoolExpr::equality(parser)));
+            let y = BoolExpr::equality(parser)?;
+            parser.finish_node();
+            x = BoolExpr::new(BoolExprKind::And(x, y), parser.span_from(start));
         }
-        x
+        Ok(x)
     }
 
-    fn equality(parser : &mut Parser) -> Box<Self> {
+    fn equality(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        let start = parser.lookahead.span;
+        let cp = parser.checkpoint();
         // equ -> rel
-        let mut x = BoolExpr::rel(parser);
+        let mut x = BoolExpr::rel(parser)?;
         while parser.lookahead.tag == Tag::RelOp {
-            if parser.lookahead.info == TokenInfo::Equ {
+            let eq = match parser.lookahead.info {
                 // equ -> rel == equ
-                parser.shift_lookahead();
-                x = Box::new(BoolExpr::Eq(x, BoolExpr::rel(parser)));
-            } else if parser.lookahead.info == TokenInfo::Neq {
+                TokenInfo::Equ => true,
                 // equ -> rel != equ
-                parser.shift_lookahead();
-                x = Box::new(BoolExpr::Neq(x, BoolExpr::rel(parser)));
-            }
+                TokenInfo::Neq => false,
+                // Relational operators are not associative.
+                _ => break,
+            };
+            parser.start_node_at(cp, NodeKind::BinaryExpr);
+            parser.shift_lookahead();
+            let y = BoolExpr::rel(parser)?;
+            parser.finish_node();
+            let kind = if eq { BoolExprKind::Eq(x, y) } else { BoolExprKind::Neq(x, y) };
+            x = BoolExpr::new(kind, parser.span_from(start));
         }
-        x
+        Ok(x)
     }
 
-    fn rel(parser : &mut Parser) -> Box<Self> {
-        let x = NumExpr::parse(parser);
-        match parser.lookahead.info {
-            TokenInfo::Ge => {
-                // rel -> expr >= expr
-                parser.shift_lookahead();
-                Box::new(BoolExpr::Relop(Relop::Ge, x, NumExpr::parse(parser)))
-            }
-            TokenInfo::Gr => {
-                // rel -> expr > expr
-                parser.shift_lookahead();
-                Box::new(BoolExpr::Relop(Relop::Gr, x, NumExpr::parse(parser)))
-            }
-            TokenInfo::Leq => {
-                // rel -> expr <= expr
-                parser.shift_lookahead();
-                Box::new(BoolExpr::Relop(Relop::Leq, x, NumExpr::parse(parser)))
-            }
-            TokenInfo::Les => {
-                // rel -> expr < expr
-                parser.shift_lookahead();
-                Box::new(BoolExpr::Relop(Relop::Les, x, NumExpr::parse(parser)))
+    fn rel(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        let start = parser.lookahead.span;
+        let cp = parser.checkpoint();
+        let x = NumExpr::parse(parser)?;
+        let op = match parser.lookahead.info {
+            // rel -> expr >= expr
+            TokenInfo::Ge => Relop::Ge,
+            // rel -> expr > expr
+            TokenInfo::Gr => Relop::Gr,
+            // rel -> expr <= expr
+            TokenInfo::Leq => Relop::Leq,
+            // rel -> expr < expr
+            TokenInfo::Les => Relop::Les,
+            _ => {
+                // rel -> expr
+                let span = x.span;
+                return Ok(BoolExpr::new(BoolExprKind::NumExpr(x), span));
             }
-            _ => Box::new(BoolExpr::NumExpr(x)) // rel -> expr
-        }
+        };
+        parser.start_node_at(cp, NodeKind::BinaryExpr);
+        parser.shift_lookahead();
+        let y = NumExpr::parse(parser)?;
+        parser.finish_node();
+        Ok(BoolExpr::new(BoolExprKind::Relop(op, x, y), parser.span_from(start)))
+    }
+}
+
+#[derive(Debug)]
+pub struct NumExpr {
+    pub kind : NumExprKind,
+    pub span : Span,
+}
+
+impl PartialEq for NumExpr {
+    fn eq(&self, other : &NumExpr) -> bool {
+        self.kind == other.kind
     }
 }
 
 #[derive(PartialEq, Debug)]
-pub enum NumExpr {
+pub enum NumExprKind {
     Add(Box<NumExpr>, Box<NumExpr>),
     Sub(Box<NumExpr>, Box<NumExpr>),
     Mul(Box<NumExpr>, Box<NumExpr>),
@@ -172,67 +308,79 @@ pub enum NumExpr {
     Minus(Box<NumExpr>),
     Expr(Box<BoolExpr>),
     Loc(Box<Loc>),
+    Call(String, Vec<BoolExpr>),
     Num(u32),
+    Real(f64),
     True,
     False,
 }
 
 impl NumExpr {    
+    pub fn new(kind : NumExprKind, span : Span) -> Box<Self> {
+        Box::new(NumExpr {
+            kind,
+            span,
+        })
+    }
+
     fn generate_code(&self, code_gen : &mut CodeGenerator) -> ExprAttributes {
-        match self {
-            &NumExpr::Add(ref e1, ref e2) => {
+        match self.kind {
+            NumExprKind::Add(ref e1, ref e2) => {
                 let a1 = e1.generate_code(code_gen);
                 let a2 = e2.generate_code(code_gen);
-                let tmp = code_gen.new_temp();
-                code_gen.emit(OpCode::Add, tmp, a1.place, a2.place);
-                ExprAttributes::new(tmp)    
+                arith(code_gen, OpCode::Add, OpCode::FAdd, a1, a2)
             },
-            &NumExpr::Sub(ref e1, ref e2) => {
+            NumExprKind::Sub(ref e1, ref e2) => {
                 let a1 = e1.generate_code(code_gen);
                 let a2 = e2.generate_code(code_gen);
-                let tmp = code_gen.new_temp();
-                code_gen.emit(OpCode::Sub, tmp, a1.place, a2.place);
-                ExprAttributes::new(tmp)    
+                arith(code_gen, OpCode::Sub, OpCode::FSub, a1, a2)
             },
-            &NumExpr::Mul(ref e1, ref e2) => {
+            NumExprKind::Mul(ref e1, ref e2) => {
                 let a1 = e1.generate_code(code_gen);
                 let a2 = e2.generate_code(code_gen);
-                let tmp = code_gen.new_temp();
-                code_gen.emit(OpCode::Mul, tmp, a1.place, a2.place);
-                ExprAttributes::new(tmp)    
+                arith(code_gen, OpCode::Mul, OpCode::FMul, a1, a2)
             },
-            &NumExpr::Div(ref e1, ref e2) => {
+            NumExprKind::Div(ref e1, ref e2) => {
                 let a1 = e1.generate_code(code_gen);
                 let a2 = e2.generate_code(code_gen);
-                let tmp = code_gen.new_temp();
-                code_gen.emit(OpCode::Div, tmp, a1.place, a2.place);
-                ExprAttributes::new(tmp)    
+                arith(code_gen, OpCode::Div, OpCode::FDiv, a1, a2)
             },
-            &NumExpr::Not(ref e1) => {
+            NumExprKind::Not(ref e1) => {
                 let a1 = e1.generate_code(code_gen);
                 let tmp = code_gen.new_temp();
                 code_gen.emit(OpCode::Not, tmp, a1.place, a1.place);
                 ExprAttributes::new(tmp)    
             },
-            &NumExpr::Minus(ref e1) => {
+            NumExprKind::Minus(ref e1) => {
                 let a1 = e1.generate_code(code_gen);
                 let tmp = code_gen.new_temp();
-                code_gen.emit(OpCode::Minus, tmp, a1.place, a1.place);
-                ExprAttributes::new(tmp)    
+                if a1.basic_type == BasicType::Float {
+                    code_gen.emit(OpCode::FMinus, tmp, a1.place, a1.place);
+                    ExprAttributes::float(tmp)
+                } else {
+                    code_gen.emit(OpCode::Minus, tmp, a1.place, a1.place);
+                    ExprAttributes::new(tmp)    
+                }
             },
-            &NumExpr::Expr(ref e1) => {
+            NumExprKind::Expr(ref e1) => {
                 e1.generate_code(code_gen)
             },
-            &NumExpr::Loc(ref l) => {
+            NumExprKind::Loc(ref l) => {
                 l.generate_code(code_gen)
             },
-            &NumExpr::Num(x) => {
+            NumExprKind::Call(ref name, ref args) => {
+                generate_call(code_gen, name, args)
+            },
+            NumExprKind::Num(x) => {
                 ExprAttributes::new(Address::new_constant(x as i32))    
             },
-            &NumExpr::True => {
+            NumExprKind::Real(x) => {
+                ExprAttributes::float(code_gen.new_float_constant(x))
+            },
+            NumExprKind::True => {
                 ExprAttributes::new(Address::new_constant(1))   
             },
-            &NumExpr::False => {
+            NumExprKind::False => {
                 ExprAttributes::new(Address::new_constant(0))  
             },
         }
@@ -240,163 +388,215 @@ impl NumExpr {
 }
 
 impl ParseNode for NumExpr {
-    fn parse(parser : &mut Parser) -> Box<Self> {
-        let mut x = NumExpr::term(parser);
+    fn parse(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        let start = parser.lookahead.span;
+        let cp = parser.checkpoint();
+        let mut x = NumExpr::term(parser)?;
         loop {
-            match parser.lookahead.info {
-                TokenInfo::Add => {
-                    // expr -> term + expr
-                    parser.shift_lookahead();
-                    x = Box::new(NumExpr::Add(x, NumExpr::term(parser)));
-                },
-                TokenInfo::Sub => {
-                    // expr -> term - expr
-                    parser.shift_lookahead();
-                    x = Box::new(NumExpr::Sub(x, NumExpr::term(parser)));
-                },
+            let add = match parser.lookahead.info {
+                // expr -> term + expr
+                TokenInfo::Add => true,
+                // expr -> term - expr
+                TokenInfo::Sub => false,
                 _ => break, // expr -> term
-            }
+            };
+            parser.start_node_at(cp, NodeKind::BinaryExpr);
+            parser.shift_lookahead();
+            let y = NumExpr::term(parser)?;
+            parser.finish_node();
+            let kind = if add { NumExprKind::Add(x, y) } else { NumExprKind::Sub(x, y) };
+            x = NumExpr::new(kind, parser.span_from(start));
         }
-        x
+        Ok(x)
     }
 }
 
 impl NumExpr {
-    fn term(parser : &mut Parser) -> Box<Self> {
-        let mut x = NumExpr::unary(parser);
+    fn term(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        let start = parser.lookahead.span;
+        let cp = parser.checkpoint();
+        let mut x = NumExpr::unary(parser)?;
         loop {
-            match parser.lookahead.info {
-                TokenInfo::Mul => {
-                    // term -> unary * term
-                    parser.shift_lookahead();
-                    x = Box::new(NumExpr::Mul(x, NumExpr::unary(parser)));
-                },
-                TokenInfo::Div => {
-                    // term -> unary / term
-                    parser.shift_lookahead();
-                    x = Box::new(NumExpr::Div(x, NumExpr::unary(parser)));
-                },
+            let mul = match parser.lookahead.info {
+                // term -> unary * term
+                TokenInfo::Mul => true,
+                // term -> unary / term
+                TokenInfo::Div => false,
                 _ => break, // term -> unary
-            }
+            };
+            parser.start_node_at(cp, NodeKind::BinaryExpr);
+            parser.shift_lookahead();
+            let y = NumExpr::unary(parser)?;
+            parser.finish_node();
+            let kind = if mul { NumExprKind::Mul(x, y) } else { NumExprKind::Div(x, y) };
+            x = NumExpr::new(kind, parser.span_from(start));
         }
-        x
+        Ok(x)
     }
 
-    fn unary(parser : &mut Parser) -> Box<Self> {
-        match parser.lookahead.info {
-            TokenInfo::Sub => {
-                // unary -> -unary
-                parser.shift_lookahead();
-                Box::new(NumExpr::Minus(NumExpr::unary(parser)))
-            }, 
-            TokenInfo::Not => {
-                // unary -> !unary
-                parser.shift_lookahead();
-                Box::new(NumExpr::Not(NumExpr::unary(parser)))
-            },   
-            _ => NumExpr::factor(parser), // unary -> factor
-        }
+    fn unary(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        let start = parser.lookahead.span;
+        let minus = match parser.lookahead.info {
+            // unary -> -unary
+            TokenInfo::Sub => true,
+            // unary -> !unary
+            TokenInfo::Not => false,
+            _ => return NumExpr::factor(parser), // unary -> factor
+        };
+        parser.start_node(NodeKind::UnaryExpr);
+        parser.shift_lookahead();
+        let x = NumExpr::unary(parser)?;
+        parser.finish_node();
+        let kind = if minus { NumExprKind::Minus(x) } else { NumExprKind::Not(x) };
+        Ok(NumExpr::new(kind, parser.span_from(start)))
     }
 
-    fn factor(parser : &mut Parser) -> Box<Self> {
-        match parser.lookahead.tag {
+    fn factor(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        let start = parser.lookahead.span;
+        let call = parser.lookahead.tag == Tag::Ide && parser.peek().tag == Tag::LParen;
+        let kind = match parser.lookahead.tag {
+            // factor -> ID ( args )
+            Tag::Ide if call => {
+                let (name, args) = parse_call(parser)?;
+                return Ok(NumExpr::new(NumExprKind::Call(name, args), parser.span_from(start)));
+            },
             // factor -> loc 
-            Tag::Ide => Box::new(NumExpr::Loc(Loc::parse(parser))),             
-            Tag::Num => {
-                // factor -> num
-                if let TokenInfo::Num(x) = parser.shift_lookahead().info {
-                    Box::new(NumExpr::Num(x))
-                } else { unreachable!("Wrong token info for num.") }
-            },            
-            Tag::True => {
-                // factor -> True
-                parser.shift_lookahead();
-                Box::new(NumExpr::True)
-            }, 
-            Tag::False => {
-                // factor -> False
-                parser.shift_lookahead();
-                Box::new(NumExpr::False)
-            },            
+            Tag::Ide => {
+                let l = Loc::parse(parser)?;
+                let span = l.span;
+                return Ok(NumExpr::new(NumExprKind::Loc(l), span));
+            },
             Tag::LParen => {
                 // factor -> (bool)
+                parser.start_node(NodeKind::ParenExpr);
                 parser.shift_lookahead();
-                let b = BoolExpr::parse(parser);
-                parser.match_lookahead(Tag::RParen);
-                Box::new(NumExpr::Expr(b))
+                let b = BoolExpr::parse(parser)?;
+                parser.match_lookahead(Tag::RParen)?;
+                parser.finish_node();
+                return Ok(NumExpr::new(NumExprKind::Expr(b), parser.span_from(start)));
             },
-            _ => panic!("Wrong token for factor: {:?}", parser.lookahead.tag),
-        }
+            // factor -> num
+            Tag::Num => match parser.lookahead.info {
+                TokenInfo::Num(x) => NumExprKind::Num(x),
+                _ => unreachable!("Wrong token info for num."),
+            },
+            // factor -> real
+            Tag::Real => match parser.lookahead.info {
+                TokenInfo::Real(x) => NumExprKind::Real(x),
+                _ => unreachable!("Wrong token info for real."),
+            },
+            // factor -> True
+            Tag::True => NumExprKind::True,
+            // factor -> False
+            Tag::False => NumExprKind::False,
+            _ => return Err(parser.error("expression")),
+        };
+        parser.start_node(NodeKind::Literal);
+        parser.shift_lookahead();
+        parser.finish_node();
+        Ok(NumExpr::new(kind, parser.span_from(start)))
     }
 }
 
 
+#[derive(Debug)]
+pub struct Loc {
+    pub kind : LocKind,
+    pub span : Span,
+    pub decl : Option<DeclId>,  // Set by the resolver.
+}
+
+// Nor is the declaration found by the resolver.
+impl PartialEq for Loc {
+    fn eq(&self, other : &Loc) -> bool {
+        self.kind == other.kind
+    }
+}
+
 #[derive(PartialEq, Debug)]
-pub enum Loc {
+pub enum LocKind {
     Index(String, Vec<Box<BoolExpr>>),
     Ide(String),
 }
 
 impl Loc {
+    pub fn new(kind : LocKind, span : Span) -> Box<Self> {
+        Box::new(Loc {
+            kind,
+            span,
+            decl : None,
+        })
+    }
+
     pub fn generate_code(&self, code_gen : &mut CodeGenerator) -> ExprAttributes {
-        match self {
-            &Loc::Index(ref s, ref v) => {
+        match self.kind {
+            LocKind::Index(ref s, ref v) => {
                 // TODO: cloning the array every time is not the best we can do.
-                let info = match code_gen.sym_table.get_ide(s) {
+                // The number of indices was checked by the type checker.
+                let (info, basic_type) = match code_gen.sym_table.get_ide(s) {
                     Some(info) => {
-                        info.typeinfo.dim_width.clone()    
+                        (info.typeinfo.dim_width.clone(), info.typeinfo.basic_type)
+                    },
+                    None => {
+                        code_gen.error(undeclared(s, self.span));
+                        return ExprAttributes::new(code_gen.new_temp());
                     },
-                    None => Vec::new(),
                 };
                 
-                if v.len() != info.len() - 1 {
-                    panic!("Wrong dimension for array.");
-                }
-                
                 let range = 0..v.len();
                 let offset = code_gen.new_pointer();
                 code_gen.emit(OpCode::Add, offset, Address::new_constant(0), Address::new_constant(0));
                 for i in range {
-                    let tmp = v[i].generate_code(code_gen).place;
-                    code_gen.emit(OpCode::Mul, tmp, tmp, Address::new_constant(info[i+1] as i32));
+                    // The index may be a variable, it is scaled into a new temp.
+                    let index = v[i].generate_code(code_gen).place;
+                    let tmp = code_gen.new_temp();
+                    code_gen.emit(OpCode::Mul, tmp, index, Address::new_constant(info[i+1] as i32));
                     code_gen.emit(OpCode::Add, offset, offset, tmp);
                 }                
-                ExprAttributes { place : offset }
+                ExprAttributes { place : offset, basic_type }
+                }
+            LocKind::Ide(ref s) => {
+                let addr = code_gen.sym_table.get_ide(s)
+                    .map(|info| (info.address, info.typeinfo.basic_type));
+                match addr {
+                    Some((addr, t)) => ExprAttributes { place : addr, basic_type : t },
+                    None => {
+                        code_gen.error(undeclared(s, self.span));
+                        ExprAttributes::new(code_gen.new_temp())
+                    },
                 }
-            &Loc::Ide(ref s) => {
-                let info = code_gen.sym_table
-                            .get_ide(s)
-                            .expect("Undeclared identifier in current scope.");
-                ExprAttributes { place : info.address }
             }
         }
     }
 }
 
 impl ParseNode for Loc {
-    fn parse(parser : &mut Parser) -> Box<Self> {
+    fn parse(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        let start = parser.lookahead.span;
         if parser.lookahead.tag == Tag::Ide {
+            parser.start_node(NodeKind::Loc);
             let inf = parser.shift_lookahead().info;
             let mut v = Vec::new();
             while parser.lookahead.tag == Tag::LArrParen {
                 parser.shift_lookahead();
-                let b = BoolExpr::parse(parser);
+                let b = BoolExpr::parse(parser)?;
                 v.push(b);
-                parser.match_lookahead(Tag::RArrParen);
+                parser.match_lookahead(Tag::RArrParen)?;
             }
+            parser.finish_node();
             if let TokenInfo::Ide(s) = inf {
                 if v.len() > 0 {
                     // loc -> loc[bool]
-                    Box::new(Loc::Index(s, v))
+                    Ok(Loc::new(LocKind::Index(s, v), parser.span_from(start)))
                 } else {
                     // loc -> ID
-                    Box::new(Loc::Ide(s))
+                    Ok(Loc::new(LocKind::Ide(s), parser.span_from(start)))
                 }
             } else {
                 unreachable!("Wrong token info inside identifier.")
             }
         } else {
-            panic!("Expected ide inside lvalue. Found: {:?}", parser.lookahead.tag)
+            Err(parser.error(Tag::Ide.describe()))
         }
     }
 }
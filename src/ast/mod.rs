@@ -3,3 +3,5 @@
 */
 pub mod expression;
 pub mod statement;
+pub mod visit;
+pub mod serialize;
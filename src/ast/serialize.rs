@@ -0,0 +1,405 @@
+/*
+    Serialization of the tree for external tools, as JSON or S-expressions,
+    and as a Graphviz graph to look at. Every node has its type, its kind when
+    the type has several, its span and its children in named fields, the same
+    in all formats:
+
+        {"node":"NumExpr","kind":"Add","span":{"lo":0,"hi":5,"line":1,"col":1},"lhs":...,"rhs":...}
+        (NumExpr Add :span (0 5 1 1) :lhs ... :rhs ...)
+
+    The lexer only makes finite reals, but a tree built or rewritten by hand
+    may hold others: JSON writes them as null, S-expressions as the Scheme
+    +inf.0, -inf.0 and +nan.0.
+*/
+use ast::statement::{Program, Item, ConstDecl, Function, Block, Decl, Type, Statement, StatementKind, BasicType, Case, CaseLabel};
+use ast::expression::{BoolExpr, BoolExprKind, NumExpr, NumExprKind, Loc, LocKind, Relop};
+use lexer::Span;
+
+enum Value {
+    Node(Node),
+    List(Vec<Value>),
+    Str(String),
+    Int(i64),
+    Float(f64),
+    Ident(&'static str),    // Enum variant, like an operator or a basic type.
+}
+
+struct Node {
+    node   : &'static str,
+    kind   : Option<&'static str>,
+    span   : Option<Span>,
+    fields : Vec<(&'static str, Value)>,
+}
+
+impl Node {
+    fn new(node : &'static str, kind : Option<&'static str>, span : Option<Span>) -> Self {
+        Node {
+            node,
+            kind,
+            span,
+            fields : Vec::new(),
+        }
+    }
+
+    fn field(mut self, name : &'static str, v : Value) -> Self {
+        self.fields.push((name, v));
+        self
+    }
+}
+
+pub fn to_json(p : &Program) -> String {
+    let mut out = String::new();
+    write_json(&program(p), &mut out);
+    out
+}
+
+pub fn to_sexp(p : &Program) -> String {
+    let mut out = String::new();
+    write_sexp(&program(p), &mut out);
+    out
+}
+
+// Graphviz graph with a box for each node and edges named after the fields.
+pub fn to_dot(p : &Program) -> String {
+    let mut out = String::from("digraph ast {\n    node [shape=box];\n");
+    let mut next = 0;
+    if let Value::Node(ref n) = program(p) {
+        write_dot(n, &mut next, &mut out);
+    }
+    out.push_str("}\n");
+    out
+}
+
+/* From the tree to values. */
+
+// `items` is left out when there are none, `block` when there is a main.
+fn program(p : &Program) -> Value {
+    let items = if p.items.is_empty() {
+        None
+    } else {
+        Some(Value::List(p.items.iter().map(item).collect()))
+    };
+    let n = optional(Node::new("Program", None, Some(p.span)), "items", items);
+    Value::Node(optional(n, "block", p.block.as_ref().map(|b| block(b))))
+}
+
+fn item(i : &Item) -> Value {
+    match *i {
+        Item::Global(ref d) => decl(d),
+        Item::Const(ref c) => const_decl(c),
+        Item::Function(ref f) => function(f),
+    }
+}
+
+fn const_decl(c : &ConstDecl) -> Value {
+    Value::Node(Node::new("Const", None, Some(c.span))
+        .field("type", Value::Ident(basic_name(c.basic_type)))
+        .field("name", Value::Str(c.name.clone()))
+        .field("value", bool_expr(&c.value)))
+}
+
+// Procedures have no return type.
+fn function(f : &Function) -> Value {
+    let n = Node::new("Function", None, Some(f.span))
+        .field("name", Value::Str(f.name.clone()))
+        .field("params", Value::List(f.params.iter().map(decl).collect()));
+    Value::Node(optional(n, "ret", f.ret.map(|t| Value::Ident(basic_name(t))))
+        .field("body", block(&f.body)))
+}
+
+fn block(b : &Block) -> Value {
+    Value::Node(Node::new("Block", None, Some(b.span))
+        .field("decls", Value::List(b.decls.iter().map(|d| decl(d)).collect()))
+        .field("stmts", Value::List(b.stmts.iter().map(|s| statement(s)).collect())))
+}
+
+fn decl(d : &Decl) -> Value {
+    Value::Node(Node::new("Decl", None, Some(d.span))
+        .field("type", type_value(&d.type_id))
+        .field("name", Value::Str((*d.id).clone())))
+}
+
+// Types have no span. Dimensions are the number of elements, not the widths.
+fn type_value(t : &Type) -> Value {
+    let basic = basic_name(t.basic_type);
+    let dims = t.dims().into_iter().map(|d| Value::Int(d as i64)).collect();
+    Value::Node(Node::new("Type", None, None)
+        .field("basic", Value::Ident(basic))
+        .field("dims", Value::List(dims)))
+}
+
+fn basic_name(t : BasicType) -> &'static str {
+    match t {
+        BasicType::Int => "Int",
+        BasicType::Float => "Float",
+        BasicType::Bool => "Bool",
+    }
+}
+
+fn statement(s : &Statement) -> Value {
+    let node = |kind| Node::new("Statement", Some(kind), Some(s.span));
+    let n = match s.kind {
+        StatementKind::Assign(ref l, ref e) => node("Assign")
+            .field("target", loc(l))
+            .field("value", bool_expr(e)),
+        StatementKind::If(ref e, ref s1) => node("If")
+            .field("cond", bool_expr(e))
+            .field("then", statement(s1)),
+        StatementKind::IfElse(ref e, ref s1, ref s2) => node("IfElse")
+            .field("cond", bool_expr(e))
+            .field("then", statement(s1))
+            .field("else", statement(s2)),
+        StatementKind::While(ref e, ref s1) => node("While")
+            .field("cond", bool_expr(e))
+            .field("body", statement(s1)),
+        StatementKind::For(ref f) => {
+            let n = optional(node("For"), "decl", f.decl.as_ref().map(|d| decl(d)));
+            let n = optional(n, "init", f.init.as_ref().map(|s| statement(s)));
+            let n = optional(n, "cond", f.cond.as_ref().map(|e| bool_expr(e)));
+            optional(n, "step", f.step.as_ref().map(|s| statement(s)))
+                .field("body", statement(&f.body))
+        },
+        StatementKind::DoWhile(ref s1, ref e) => node("DoWhile")
+            .field("body", statement(s1))
+            .field("cond", bool_expr(e)),
+        StatementKind::Switch(ref e, ref cases) => node("Switch")
+            .field("value", bool_expr(e))
+            .field("cases", Value::List(cases.iter().map(case).collect())),
+        StatementKind::Labeled(ref name, ref s1) => node("Labeled")
+            .field("label", Value::Str(name.clone()))
+            .field("body", statement(s1)),
+        StatementKind::Break(ref label) => {
+            optional(node("Break"), "label", label.clone().map(Value::Str))
+        },
+        StatementKind::Continue(ref label) => {
+            optional(node("Continue"), "label", label.clone().map(Value::Str))
+        },
+        StatementKind::Return(ref e) => {
+            optional(node("Return"), "value", e.as_ref().map(|e| bool_expr(e)))
+        },
+        StatementKind::Call(ref name, ref args) => node("Call")
+            .field("name", Value::Str(name.clone()))
+            .field("args", Value::List(args.iter().map(bool_expr).collect())),
+        StatementKind::BlockStmt(ref b) => node("BlockStmt")
+            .field("block", block(b)),
+        StatementKind::Error => node("Error"),
+    };
+    Value::Node(n)
+}
+
+fn case(c : &Case) -> Value {
+    let stmts = Value::List(c.stmts.iter().map(|s| statement(s)).collect());
+    let n = match c.label {
+        CaseLabel::Int(x) => Node::new("Case", Some("Int"), Some(c.span)).field("value", Value::Int(x as i64)),
+        CaseLabel::Bool(b) => {
+            let b = if b { "True" } else { "False" };
+            Node::new("Case", Some("Bool"), Some(c.span)).field("value", Value::Ident(b))
+        },
+        CaseLabel::Default => Node::new("Case", Some("Default"), Some(c.span)),
+    };
+    Value::Node(n.field("stmts", stmts))
+}
+
+// Optional parts of a node, like the label of a break, are left out when
+// they are not written.
+fn optional(n : Node, name : &'static str, v : Option<Value>) -> Node {
+    match v {
+        Some(v) => n.field(name, v),
+        None => n,
+    }
+}
+
+fn bool_expr(e : &BoolExpr) -> Value {
+    let node = |kind| Node::new("BoolExpr", Some(kind), Some(e.span));
+    let binary = |kind, a : &BoolExpr, b : &BoolExpr| node(kind)
+        .field("lhs", bool_expr(a))
+        .field("rhs", bool_expr(b));
+    let n = match e.kind {
+        BoolExprKind::Or(ref a, ref b) => binary("Or", a, b),
+        BoolExprKind::And(ref a, ref b) => binary("And", a, b),
+        BoolExprKind::Eq(ref a, ref b) => binary("Eq", a, b),
+        BoolExprKind::Neq(ref a, ref b) => binary("Neq", a, b),
+        BoolExprKind::Relop(ref op, ref a, ref b) => {
+            let op = match *op {
+                Relop::Ge => "Ge",
+                Relop::Gr => "Gr",
+                Relop::Leq => "Leq",
+                Relop::Les => "Les",
+            };
+            node("Relop")
+                .field("op", Value::Ident(op))
+                .field("lhs", num_expr(a))
+                .field("rhs", num_expr(b))
+        },
+        BoolExprKind::NumExpr(ref a) => node("NumExpr").field("expr", num_expr(a)),
+    };
+    Value::Node(n)
+}
+
+fn num_expr(e : &NumExpr) -> Value {
+    let node = |kind| Node::new("NumExpr", Some(kind), Some(e.span));
+    let binary = |kind, a : &NumExpr, b : &NumExpr| node(kind)
+        .field("lhs", num_expr(a))
+        .field("rhs", num_expr(b));
+    let n = match e.kind {
+        NumExprKind::Add(ref a, ref b) => binary("Add", a, b),
+        NumExprKind::Sub(ref a, ref b) => binary("Sub", a, b),
+        NumExprKind::Mul(ref a, ref b) => binary("Mul", a, b),
+        NumExprKind::Div(ref a, ref b) => binary("Div", a, b),
+        NumExprKind::Not(ref a) => node("Not").field("operand", num_expr(a)),
+        NumExprKind::Minus(ref a) => node("Minus").field("operand", num_expr(a)),
+        NumExprKind::Expr(ref b) => node("Expr").field("expr", bool_expr(b)),
+        NumExprKind::Loc(ref l) => node("Loc").field("loc", loc(l)),
+        NumExprKind::Call(ref name, ref args) => node("Call")
+            .field("name", Value::Str(name.clone()))
+            .field("args", Value::List(args.iter().map(bool_expr).collect())),
+        NumExprKind::Num(x) => node("Num").field("value", Value::Int(x as i64)),
+        NumExprKind::Real(x) => node("Real").field("value", Value::Float(x)),
+        NumExprKind::True => node("True"),
+        NumExprKind::False => node("False"),
+    };
+    Value::Node(n)
+}
+
+fn loc(l : &Loc) -> Value {
+    let node = |kind| Node::new("Loc", Some(kind), Some(l.span));
+    let n = match l.kind {
+        LocKind::Ide(ref s) => node("Ide").field("name", Value::Str(s.clone())),
+        LocKind::Index(ref s, ref v) => node("Index")
+            .field("name", Value::Str(s.clone()))
+            .field("indices", Value::List(v.iter().map(|e| bool_expr(e)).collect())),
+    };
+    Value::Node(n)
+}
+
+/* Writers. */
+
+fn write_json(v : &Value, out : &mut String) {
+    match *v {
+        Value::Node(ref n) => {
+            out.push_str(&format!("{{\"node\":\"{}\"", n.node));
+            if let Some(kind) = n.kind {
+                out.push_str(&format!(",\"kind\":\"{}\"", kind));
+            }
+            if let Some(sp) = n.span {
+                out.push_str(&format!(",\"span\":{{\"lo\":{},\"hi\":{},\"line\":{},\"col\":{}}}",
+                                      sp.lo, sp.hi, sp.line, sp.col));
+            }
+            for &(name, ref field) in &n.fields {
+                out.push_str(&format!(",\"{}\":", name));
+                write_json(field, out);
+            }
+            out.push('}');
+        },
+        Value::List(ref items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json(item, out);
+            }
+            out.push(']');
+        },
+        Value::Str(ref s) => write_string(s, out),
+        Value::Int(x) => out.push_str(&format!("{}", x)),
+        Value::Float(x) if !x.is_finite() => out.push_str("null"),
+        Value::Float(x) => out.push_str(&format!("{:?}", x)),
+        Value::Ident(s) => out.push_str(&format!("\"{}\"", s)),
+    }
+}
+
+fn write_sexp(v : &Value, out : &mut String) {
+    match *v {
+        Value::Node(ref n) => {
+            out.push('(');
+            out.push_str(n.node);
+            if let Some(kind) = n.kind {
+                out.push(' ');
+                out.push_str(kind);
+            }
+            if let Some(sp) = n.span {
+                out.push_str(&format!(" :span ({} {} {} {})", sp.lo, sp.hi, sp.line, sp.col));
+            }
+            for &(name, ref field) in &n.fields {
+                out.push_str(&format!(" :{} ", name));
+                write_sexp(field, out);
+            }
+            out.push(')');
+        },
+        Value::List(ref items) => {
+            out.push('(');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                write_sexp(item, out);
+            }
+            out.push(')');
+        },
+        Value::Str(ref s) => write_string(s, out),
+        Value::Int(x) => out.push_str(&format!("{}", x)),
+        Value::Float(x) if x.is_nan() => out.push_str("+nan.0"),
+        Value::Float(x) if x.is_infinite() => out.push_str(if x > 0.0 { "+inf.0" } else { "-inf.0" }),
+        Value::Float(x) => out.push_str(&format!("{:?}", x)),
+        Value::Ident(s) => out.push_str(s),
+    }
+}
+
+// Writes the node and its children, returns the id of the node. Fields that
+// are not nodes are lines of its label.
+fn write_dot(n : &Node, next : &mut usize, out : &mut String) -> usize {
+    let id = *next;
+    *next += 1;
+    let mut label = String::from(n.node);
+    if let Some(kind) = n.kind {
+        label.push_str(&format!(" {}", kind));
+    }
+    if let Some(sp) = n.span {
+        label.push_str(&format!("\\n{}:{}", sp.line, sp.col));
+    }
+    let mut edges = Vec::new();
+    for &(name, ref field) in &n.fields {
+        match *field {
+            Value::Node(ref child) => edges.push((String::from(name), child)),
+            Value::List(ref items) if items.iter().all(is_node) => {
+                for (i, item) in items.iter().enumerate() {
+                    if let Value::Node(ref child) = *item {
+                        edges.push((format!("{}[{}]", name, i), child));
+                    }
+                }
+            },
+            ref v => {
+                let mut s = String::new();
+                write_sexp(v, &mut s);
+                let s = s.replace('\\', "\\\\").replace('"', "\\\"");
+                label.push_str(&format!("\\n{}: {}", name, s));
+            },
+        }
+    }
+    out.push_str(&format!("    n{} [label=\"{}\"];\n", id, label));
+    for (name, child) in edges {
+        let child_id = write_dot(child, next, out);
+        out.push_str(&format!("    n{} -> n{} [label=\"{}\"];\n", id, child_id, name));
+    }
+    id
+}
+
+fn is_node(v : &Value) -> bool {
+    matches!(*v, Value::Node(_))
+}
+
+// Quoted string, with the escapes JSON and S-expressions have in common.
+fn write_string(s : &str, out : &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -1,88 +1,395 @@
-use parser::{ParseNode, Parser};
-use lexer::{TokenInfo, Tag};
-use ast::expression::{BoolExpr, Loc};
-use code_generator::{CodeGenerator, OpCode, Address};
+use parser::{ParseNode, Parser, ParseError};
+use lexer::{Token, TokenInfo, Tag, Span};
+use ast::expression::{BoolExpr, Loc, LocKind, parse_call, generate_call};
+use code_generator::{CodeGenerator, OpCode, Address, Variable};
+use ast::expression::{to_float, to_int};
+use cst::{NodeKind, Checkpoint};
 
-struct StatementAttributes {
-    break_list : Vec<usize>,
+// Spans are not part of the structural equality of the tree.
+#[derive(Debug)]
+pub struct Program {
+    pub items : Vec<Item>,
+    pub block : Option<Box<Block>>,     // Entry point, unless there is a main.
+    pub span  : Span,
+}
+
+impl PartialEq for Program {
+    fn eq(&self, other : &Program) -> bool {
+        self.items == other.items && self.block == other.block
+    }
 }
 
 #[derive(PartialEq, Debug)]
-pub struct Program {
-    pub block : Box<Block>,    
+pub enum Item {
+    Global(Box<Decl>),
+    Const(Box<ConstDecl>),
+    Function(Box<Function>),
 }
 
 impl Program {
+    pub fn functions(&self) -> Vec<&Function> {
+        self.items.iter().filter_map(|i| match *i {
+            Item::Function(ref f) => Some(&**f),
+            _ => None,
+        }).collect()
+    }
+
+    // Globals and constants are in static storage, visible from all the
+    // functions. The code starts with the functions, jumped over to set the
+    // constants and run the block or call main.
     pub fn generate_code(&self, code_gen : &mut CodeGenerator) {
-        self.block.generate_code(code_gen);
+        let funcs = self.functions();
+        for f in &funcs {
+            let params = f.params.iter().map(|p| p.type_id.basic_type).collect();
+            code_gen.declare_function(&f.name, params, f.ret);
+        }
+        let mut consts = Vec::new();
+        for item in &self.items {
+            match *item {
+                Item::Global(ref d) => {
+                    d.generate_global(code_gen);
+                },
+                Item::Const(ref c) => {
+                    let t = Type { basic_type : c.basic_type, element_width : 4, dim_width : Vec::new() };
+                    let addr = declare_global(code_gen, &c.name, t, c.span);
+                    consts.push((c, addr));
+                },
+                Item::Function(_) => (),
+            }
+        }
+        if !funcs.is_empty() {
+            let lblbegin = code_gen.emit_label();
+            let jmp = code_gen.emit_jump(OpCode::Goto, lblbegin, Address::null_address());
+            for f in &funcs {
+                f.generate_code(code_gen);
+            }
+            let lblmain = code_gen.emit_label();
+            code_gen.patch_jump(jmp, lblmain);
+        }
+        for (c, addr) in consts {
+            let battr = c.value.generate_code(code_gen);
+            let value = match c.basic_type {
+                BasicType::Float => to_float(code_gen, battr),
+                _ => to_int(code_gen, battr),
+            };
+            code_gen.emit(OpCode::Mov, addr, value, value);
+        }
+        match self.block {
+            Some(ref b) => b.generate_code(code_gen),
+            None => { code_gen.emit_call("main", Vec::new()); },
+        }
+        code_gen.patch_calls();
     }
 }
 
 impl ParseNode for Program {
-    fn parse(parser : &mut Parser) -> Box<Self> {
-        //program -> block
-        Box::new(Program {            
-            block : Block::parse(parser),
-        })
+    fn parse(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        //program -> items | items block
+        let start = parser.lookahead.span;
+        parser.start_node(NodeKind::Program);
+        let mut items = Vec::new();
+        while matches!(parser.lookahead.tag, Tag::Type | Tag::Void | Tag::Const) {
+            let item_start = parser.lookahead.span;
+            let (depth, cp) = (parser.node_depth(), parser.checkpoint());
+            match item(parser) {
+                Ok(i) => items.push(i),
+                Err(e) => {
+                    // Skip the rest of the item, nothing takes its place.
+                    parser.errors.push(e);
+                    parser.unwind_nodes(depth);
+                    parser.start_node_at(cp, NodeKind::Error);
+                    if parser.lookahead.span == item_start {
+                        parser.shift_lookahead();
+                    }
+                    parser.synchronize_item();
+                    parser.finish_node();
+                },
+            }
+        }
+        // Anything else starts the block, that reports missing braces.
+        let block = if parser.lookahead.tag == Tag::Eof {
+            None
+        } else {
+            Some(Block::parse(parser)?)
+        };
+        parser.finish_node();
+        Ok(Box::new(Program {            
+            items,
+            block,
+            span  : parser.span_from(start),
+        }))
     }
 }
 
-#[derive(PartialEq, Debug)]
+// item -> decl | const basic ID = bool; | func
+// Declarations and functions with a return type both start with a type and
+// a name, then the parenthesis tells them apart.
+fn item(parser : &mut Parser) -> Result<Item, ParseError> {
+    let start = parser.lookahead.span;
+    let cp = parser.checkpoint();
+    match parser.lookahead.tag {
+        Tag::Const => Ok(Item::Const(ConstDecl::parse(parser)?)),
+        Tag::Void => {
+            parser.shift_lookahead();
+            let name = ident(parser.match_lookahead(Tag::Ide)?);
+            Ok(Item::Function(function(parser, cp, start, None, name)?))
+        },
+        _ => {
+            let t = Type::parse(parser)?;
+            let name = ident(parser.match_lookahead(Tag::Ide)?);
+            if parser.lookahead.tag == Tag::LParen && t.dim_width.is_empty() {
+                return Ok(Item::Function(function(parser, cp, start, Some(t.basic_type), name)?));
+            }
+            parser.start_node_at(cp, NodeKind::Decl);
+            parser.match_lookahead(Tag::SemiColon)?;
+            parser.finish_node();
+            Ok(Item::Global(Box::new(Decl {
+                type_id : t,
+                id : Box::new(name),
+                span : parser.span_from(start),
+            })))
+        },
+    }
+}
+
+// Global constant, set before the program starts.
+#[derive(Debug)]
+pub struct ConstDecl {
+    pub name       : String,
+    pub basic_type : BasicType,
+    pub value      : Box<BoolExpr>,
+    pub span       : Span,
+}
+
+impl PartialEq for ConstDecl {
+    fn eq(&self, other : &ConstDecl) -> bool {
+        self.name == other.name && self.basic_type == other.basic_type && self.value == other.value
+    }
+}
+
+impl ParseNode for ConstDecl {
+    fn parse(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        //const -> const basic ID = bool;
+        let start = parser.lookahead.span;
+        parser.start_node(NodeKind::ConstDecl);
+        parser.match_lookahead(Tag::Const)?;
+        let t = basic_type(parser)?;
+        let name = ident(parser.match_lookahead(Tag::Ide)?);
+        parser.match_lookahead(Tag::Assign)?;
+        let value = BoolExpr::parse(parser)?;
+        parser.match_lookahead(Tag::SemiColon)?;
+        parser.finish_node();
+        Ok(Box::new(ConstDecl {
+            name,
+            basic_type : t,
+            value,
+            span       : parser.span_from(start),
+        }))
+    }
+}
+
+// Static storage for a global, kept to inspect it after the execution.
+fn declare_global(code_gen : &mut CodeGenerator, name : &str, t : Type, span : Span) -> Address {
+    let addr = code_gen.new_static();
+    let basic_type = t.basic_type;
+    if let Err(diag) = code_gen.sym_table.put_global(String::from(name), t, addr, span) {
+        code_gen.error(*diag);
+    }
+    code_gen.code.variables.push(Variable {
+        name : String::from(name),
+        address : addr,
+        basic_type,
+    });
+    addr
+}
+
+// Function, or procedure when it returns no value.
+#[derive(Debug)]
+pub struct Function {
+    pub name   : String,
+    pub params : Vec<Decl>,
+    pub ret    : Option<BasicType>,    // None for procedures.
+    pub body   : Box<Block>,
+    pub span   : Span,                 // Of the header.
+}
+
+impl PartialEq for Function {
+    fn eq(&self, other : &Function) -> bool {
+        self.name == other.name && self.params == other.params &&
+            self.ret == other.ret && self.body == other.body
+    }
+}
+
+impl Function {
+    // The activation record is a frame for the parameters, under the body.
+    fn generate_code(&self, code_gen : &mut CodeGenerator) {
+        let saved = code_gen.enter_function(&self.name);
+        code_gen.push_frame();
+        for p in &self.params {
+            p.generate_code(code_gen);
+        }
+        self.body.generate_code(code_gen);
+        code_gen.pop_frame();
+        // Procedures may end without a return.
+        let none = Address::null_address();
+        code_gen.emit(OpCode::Ret, none, none, none);
+        code_gen.leave_function(saved);
+    }
+}
+
+// func -> basic ID ( params ) block | void ID ( params ) block
+// The rest of a function, after its return type and name.
+fn function(parser : &mut Parser, cp : Option<Checkpoint>, start : Span, ret : Option<BasicType>, name : String)
+        -> Result<Box<Function>, ParseError> {
+    parser.start_node_at(cp, NodeKind::Function);
+    parser.match_lookahead(Tag::LParen)?;
+    let mut params = Vec::new();
+    if parser.lookahead.tag != Tag::RParen {
+        loop {
+            params.push(param(parser)?);
+            if parser.lookahead.tag != Tag::Comma {
+                break;
+            }
+            parser.shift_lookahead();
+        }
+    }
+    parser.match_lookahead(Tag::RParen)?;
+    let span = parser.span_from(start);
+    let body = Block::parse(parser)?;
+    parser.finish_node();
+    Ok(Box::new(Function {
+        name,
+        params,
+        ret,
+        body,
+        span,
+    }))
+}
+
+// param -> basic ID
+fn param(parser : &mut Parser) -> Result<Decl, ParseError> {
+    let start = parser.lookahead.span;
+    parser.start_node(NodeKind::Param);
+    let t = basic_type(parser)?;
+    let name = ident(parser.match_lookahead(Tag::Ide)?);
+    parser.finish_node();
+    Ok(Decl {
+        type_id : Box::new(Type {
+            basic_type : t,
+            element_width : 4,
+            dim_width : Vec::new(),
+        }),
+        id : Box::new(name),
+        span : parser.span_from(start),
+    })
+}
+
+// A type without dimensions, for parameters and results.
+fn basic_type(parser : &mut Parser) -> Result<BasicType, ParseError> {
+    parser.start_node(NodeKind::Type);
+    let t = basic(parser.match_lookahead(Tag::Type)?.info);
+    parser.finish_node();
+    Ok(t)
+}
+
+fn basic(info : TokenInfo) -> BasicType {
+    match info {
+        TokenInfo::Int   => BasicType::Int,
+        TokenInfo::Float => BasicType::Float,
+        TokenInfo::Bool  => BasicType::Bool,
+        _ => unreachable!("Wrong info for Type token.")
+    }
+}
+
+#[derive(Debug)]
 pub struct Block {
     pub decls : Vec<Box<Decl>>,
     pub stmts : Vec<Box<Statement>>,
+    pub span  : Span,
+}
+
+impl PartialEq for Block {
+    fn eq(&self, other : &Block) -> bool {
+        self.decls == other.decls && self.stmts == other.stmts
+    }
 }
 
 impl ParseNode for Block {
-    fn parse(parser : &mut Parser) -> Box<Self> {
+    fn parse(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
         let mut decls = Vec::new();
         let mut stmts = Vec::new();
+        let start = parser.lookahead.span;
+        parser.start_node(NodeKind::Block);
         //block -> decls stmts
-        parser.match_lookahead(Tag::OpenBlock);
+        if let Err(e) = parser.match_lookahead(Tag::OpenBlock) {
+            parser.errors.push(e);
+        }
         loop {
-            match parser.lookahead.tag {
+            let item_start = parser.lookahead.span;
+            let (depth, cp) = (parser.node_depth(), parser.checkpoint());
+            let res = match parser.lookahead.tag {
                 // NOTE: this way we can mix stmts and declarations.
-                Tag::Type => decls.push(Decl::parse(parser)),
-                Tag::CloseBlock => break,
-                _               => stmts.push(Statement::parse(parser)),
+                Tag::Type => Decl::parse(parser).map(|d| decls.push(d)),
+                Tag::CloseBlock | Tag::Eof => break,
+                _               => Statement::parse(parser).map(|s| stmts.push(s)),
             };
+            if let Err(e) = res {
+                // Skip the rest of the statement and leave a placeholder.
+                parser.errors.push(e);
+                parser.unwind_nodes(depth);
+                parser.start_node_at(cp, NodeKind::Error);
+                if parser.lookahead.span == item_start {
+                    parser.shift_lookahead();
+                }
+                parser.synchronize();
+                parser.finish_node();
+                stmts.push(Statement::new(StatementKind::Error, parser.span_from(item_start)));
+            }
         }
-        parser.match_lookahead(Tag::CloseBlock);
-        Box::new(Block {
+        if let Err(e) = parser.match_lookahead(Tag::CloseBlock) {
+            parser.errors.push(e);
+        }
+        parser.finish_node();
+        Ok(Box::new(Block {
             decls : decls,
             stmts : stmts,
-        })
+            span  : parser.span_from(start),
+        }))
     }
 }
 
 impl Block {
-    fn generate_code(&self, code_gen : &mut CodeGenerator) -> StatementAttributes {
-        let mut break_list = Vec::new();
+    fn generate_code(&self, code_gen : &mut CodeGenerator) {
         code_gen.push_frame();
         for d in &self.decls {
             d.generate_code(code_gen);
         }
         for v in &self.stmts {
-            let mut sa = v.generate_code(code_gen);
-            break_list.append(&mut sa.break_list);
+            v.generate_code(code_gen);
         }
         code_gen.pop_frame();
-        StatementAttributes {
-            break_list : break_list,
-        }
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Debug)]
 pub struct Decl {
     pub type_id : Box<Type>,
     pub id : Box<String>,
+    pub span : Span,
+}
+
+impl PartialEq for Decl {
+    fn eq(&self, other : &Decl) -> bool {
+        self.type_id == other.type_id && self.id == other.id
+    }
 }
 
 impl ParseNode for Decl {
-    fn parse(parser : & mut Parser) -> Box<Self> {
+    fn parse(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
         //decl -> type ID;
-        let tid = Type::parse(parser);
+        let start = parser.lookahead.span;
+        parser.start_node(NodeKind::Decl);
+        let tid = Type::parse(parser)?;
         match parser.lookahead.tag  {
             Tag::Ide => {
                     let s = {
@@ -90,25 +397,47 @@ impl ParseNode for Decl {
                             x
                         } else { unreachable!("Wrong TokenInfo for Ide.") }
                     };
-                    parser.match_lookahead(Tag::SemiColon);
-                    Box::new(Decl {
+                    parser.match_lookahead(Tag::SemiColon)?;
+                    parser.finish_node();
+                    Ok(Box::new(Decl {
                         type_id : tid,
                         id : Box::new(s), 
-                    })},
-            _ => panic!("Expecting identifier after type inside a declaration.")
+                        span : parser.span_from(start),
+                    }))},
+            _ => Err(parser.error(Tag::Ide.describe()))
         }
     }
 }
 
 impl Decl {
+    fn generate_global(&self, code_gen : &mut CodeGenerator) {
+        declare_global(code_gen, &self.id, (*self.type_id).clone(), self.span);
+    }
+
     fn generate_code(&self, code_gen : &mut CodeGenerator) {
         let addr = code_gen.new_temp();
-        code_gen.sym_table.put((*self.id).clone(), (*self.type_id).clone(), addr);
+        let res = code_gen.sym_table.put((*self.id).clone(), (*self.type_id).clone(), addr, self.span);
+        if let Err(diag) = res {
+            code_gen.error(*diag);
+        }
+        // Locals of functions live in activation records that are gone
+        // when the program ends.
+        if !code_gen.in_function() {
+            code_gen.code.variables.push(Variable {
+                name : (*self.id).clone(),
+                address : addr,
+                basic_type : self.type_id.basic_type,
+            });
+        }
     }
 }
 
-#[derive(PartialEq, Debug, Clone)]
-pub enum BasicType { Int, Float }
+// Index of a declaration in the table built by the resolver.
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+pub struct DeclId(pub usize);
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum BasicType { Int, Float, Bool }
 
 // Array dim are static.
 #[derive(PartialEq, Debug, Clone)]
@@ -118,25 +447,29 @@ pub struct Type {
     pub dim_width     : Vec<u32>,
 }
 
+impl Type {
+    // Number of elements of each dimension, recovered from the widths.
+    pub fn dims(&self) -> Vec<u32> {
+        (1..self.dim_width.len()).map(|i| self.dim_width[i - 1] / self.dim_width[i]).collect()
+    }
+}
+
 impl ParseNode for Type {
-    fn parse(parser : &mut Parser) -> Box<Self> {
+    fn parse(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
         match parser.lookahead.tag {
             Tag::Type =>{
-                let base = match parser.shift_lookahead().info {
-                    TokenInfo::Int   => BasicType::Int,
-                    TokenInfo::Float => BasicType::Float,
-                    _ => unreachable!("Wrong info for Type token.")
-                };
+                parser.start_node(NodeKind::Type);
+                let base = basic(parser.shift_lookahead().info);
                 let base_dim = 4; // All types have 4 bytes of dimension
                 let mut w = Vec::new();                
                 while parser.lookahead.tag == Tag::LArrParen {
                     // type -> type[num]
                     parser.shift_lookahead();
-                    let n = parser.match_lookahead(Tag::Num);
+                    let n = parser.match_lookahead(Tag::Num)?;
                     if let TokenInfo::Num(x) = n.info {
                         w.push(x);
-                    } else { panic!("Array dimension should be static."); }
-                    parser.match_lookahead(Tag::RArrParen);
+                    } else { unreachable!("Wrong token info for num.") }
+                    parser.match_lookahead(Tag::RArrParen)?;
                 }
                 let mut i : i32 = (w.len() as i32) - 1;
                 let mut dim = base_dim;
@@ -149,136 +482,541 @@ impl ParseNode for Type {
                 if w.len() > 0 {
                     w.push(base_dim);
                 }
+                parser.finish_node();
                 
-                Box::new(Type {
+                Ok(Box::new(Type {
                     basic_type : base,
                     element_width : base_dim, 
                     dim_width  : w,
-                })
+                }))
             },
-            _   => panic!("Wrong Token for Type")
+            _   => Err(parser.error(Tag::Type.describe()))
         }
     }
 }
 
+#[derive(Debug)]
+pub struct Statement {
+    pub kind : StatementKind,
+    pub span : Span,
+}
+
+impl PartialEq for Statement {
+    fn eq(&self, other : &Statement) -> bool {
+        self.kind == other.kind
+    }
+}
+
 #[derive(PartialEq, Debug)]
-pub enum Statement {
+pub enum StatementKind {
     Assign(Box<Loc>, Box<BoolExpr>),
     If(Box<BoolExpr>, Box<Statement>),
     IfElse(Box<BoolExpr>, Box<Statement>, Box<Statement>),
     While(Box<BoolExpr>, Box<Statement>),
-    Break,
+    For(Box<ForLoop>),
+    DoWhile(Box<Statement>, Box<BoolExpr>),
+    Switch(Box<BoolExpr>, Vec<Case>),
+    Return(Option<Box<BoolExpr>>),
+    Call(String, Vec<BoolExpr>),   // Call of a procedure, or of a function ignoring its value.
+    Labeled(String, Box<Statement>),    // Loop named by a label.
+    Break(Option<String>),
+    Continue(Option<String>),
     BlockStmt(Box<Block>),
+    Error,  // Placeholder for a statement with syntax errors.
+}
+
+// for (init; cond; step) body. A variable declared in the header is only in
+// scope in the loop, and its initial value is the init assignment.
+#[derive(PartialEq, Debug)]
+pub struct ForLoop {
+    pub decl : Option<Box<Decl>>,
+    pub init : Option<Box<Statement>>,
+    pub cond : Option<Box<BoolExpr>>,    // Always true when missing.
+    pub step : Option<Box<Statement>>,
+    pub body : Box<Statement>,
+}
+
+#[derive(Debug)]
+pub struct Case {
+    pub label : CaseLabel,
+    pub stmts : Vec<Box<Statement>>,
+    pub span  : Span,   // Of the label, up to the colon.
+}
+
+impl PartialEq for Case {
+    fn eq(&self, other : &Case) -> bool {
+        self.label == other.label && self.stmts == other.stmts
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum CaseLabel {
+    Int(i32),
+    Bool(bool),
+    Default,
 }
 
+impl CaseLabel {
+    // Value compared with the integer the switch is on.
+    fn value(self) -> Option<i32> {
+        match self {
+            CaseLabel::Int(x) => Some(x),
+            CaseLabel::Bool(b) => Some(b as i32),
+            CaseLabel::Default => None,
+        }
+    }
+}
+
+// True if some case runs whatever the value: there is a default, or the
+// switch is on a bool and both values have a case.
+pub fn covers_all(cases : &[Case]) -> bool {
+    let has = |l| cases.iter().any(|c| c.label == l);
+    has(CaseLabel::Default) || (has(CaseLabel::Bool(true)) && has(CaseLabel::Bool(false)))
+}
+
+// Switches with at least this many cases, spread over no more than twice as
+// many values, jump through a table. The others compare the cases in order.
+const MIN_TABLE_CASES : usize = 3;
+
 impl Statement {
-    fn generate_code(&self, code_gen : &mut CodeGenerator) -> StatementAttributes {
+    pub fn new(kind : StatementKind, span : Span) -> Box<Self> {
+        Box::new(Statement {
+            kind,
+            span,
+        })
+    }
+
+    fn generate_code(&self, code_gen : &mut CodeGenerator) {
         let lblbegin = code_gen.emit_label();  
-        let mut break_list = Vec::new();                             
-        match self {
-            &Statement::Assign(ref l, ref be) => {
+        match self.kind {
+            StatementKind::Assign(ref l, ref be) => {
                 let place = l.generate_code(code_gen);
                 let battr = be.generate_code(code_gen);
-                code_gen.emit(OpCode::Mov, place.place, battr.place, battr.place);
+                let value = match place.basic_type {
+                    BasicType::Int | BasicType::Bool => to_int(code_gen, battr),
+                    BasicType::Float => to_float(code_gen, battr),
+                };
+                code_gen.emit(OpCode::Mov, place.place, value, value);
             },
-            &Statement::If(ref be, ref stmt) => {
+            StatementKind::If(ref be, ref stmt) => {
                 let battr = be.generate_code(code_gen);                
                 let instr = code_gen.emit_jump(OpCode::JmpZ, lblbegin, battr.place);
-                let mut sa = stmt.generate_code(code_gen);
-                break_list.append(&mut sa.break_list);
+                stmt.generate_code(code_gen);
                 let lblafter = code_gen.emit_label();
                 code_gen.patch_jump(instr, lblafter);
             },
-            &Statement::IfElse(ref be, ref st1, ref st2) => {
+            StatementKind::IfElse(ref be, ref st1, ref st2) => {
                 let battr = be.generate_code(code_gen);
                 // Jump to else.
                 let instr = code_gen.emit_jump(OpCode::JmpZ, lblbegin, battr.place);
                 // If code.
-                let mut sa1 = st1.generate_code(code_gen);
-                break_list.append(&mut sa1.break_list);
+                st1.generate_code(code_gen);
                 let jmpendif = code_gen.emit_jump(OpCode::Goto, lblbegin, Address::null_address());
                 // Else code.
                 let lblelse = code_gen.emit_label();
                 code_gen.patch_jump(instr, lblelse);
-                let mut sa2 = st2.generate_code(code_gen);
-                break_list.append(&mut sa2.break_list);
+                st2.generate_code(code_gen);
                 let lblendelse = code_gen.emit_label();
                 code_gen.patch_jump(jmpendif, lblendelse);              
             },
-            &Statement::While(ref be, ref stmt) => {
+            StatementKind::While(..) | StatementKind::For(_) | StatementKind::DoWhile(..) => {
+                self.generate_loop(code_gen, None);
+            },
+            StatementKind::Labeled(ref name, ref stmt) => {
+                stmt.generate_loop(code_gen, Some(name.clone()));
+            },
+            StatementKind::Break(ref label) => {
+                let jmp = code_gen.emit_loop_exit(label);
+                code_gen.innermost_loop(label).breaks.push(jmp);
+            },
+            StatementKind::Continue(ref label) => {
+                let jmp = code_gen.emit_loop_exit(label);
+                code_gen.innermost_loop(label).continues.push(jmp);
+            },
+            StatementKind::Switch(ref be, ref cases) => {
+                let battr = be.generate_code(code_gen);
+                let value = to_int(code_gen, battr);
+                // Jumps to each case, and to the default or the end.
+                let mut jumps = vec![Vec::new(); cases.len()];
+                let mut others = Vec::new();
+                let values : Vec<(usize, i32)> = cases.iter().enumerate()
+                    .filter_map(|(i, c)| c.label.value().map(|v| (i, v)))
+                    .collect();
+                let min = values.iter().map(|&(_, v)| v).min().unwrap_or(0);
+                let max = values.iter().map(|&(_, v)| v).max().unwrap_or(0);
+                let range = (max as i64 - min as i64 + 1) as usize;
+                if values.len() >= MIN_TABLE_CASES && range <= 2 * values.len() {
+                    // Values out of the table go to the default.
+                    let index = code_gen.new_temp();
+                    code_gen.emit(OpCode::Sub, index, value, Address::new_constant(min));
+                    let below = code_gen.new_temp();
+                    code_gen.emit(OpCode::IsNeg, below, index, index);
+                    code_gen.emit(OpCode::Not, below, below, below);
+                    others.push(code_gen.emit_jump(OpCode::JmpZ, lblbegin, below));
+                    let above = code_gen.new_temp();
+                    code_gen.emit(OpCode::Sub, above, index, Address::new_constant(range as i32));
+                    code_gen.emit(OpCode::IsNeg, above, above, above);
+                    others.push(code_gen.emit_jump(OpCode::JmpZ, lblbegin, above));
+                    code_gen.emit_jump_table(index, range);
+                    for v in min..=max {
+                        let jmp = code_gen.emit_jump(OpCode::Goto, lblbegin, Address::null_address());
+                        match values.iter().find(|&&(_, x)| x == v) {
+                            Some(&(i, _)) => jumps[i].push(jmp),
+                            None => others.push(jmp),
+                        }
+                    }
+                } else {
+                    // The difference is zero for the matching case.
+                    for &(i, v) in &values {
+                        let diff = code_gen.new_temp();
+                        code_gen.emit(OpCode::Sub, diff, value, Address::new_constant(v));
+                        jumps[i].push(code_gen.emit_jump(OpCode::JmpZ, lblbegin, diff));
+                    }
+                    others.push(code_gen.emit_jump(OpCode::Goto, lblbegin, Address::null_address()));
+                }
+                if let Some(i) = cases.iter().position(|c| c.label == CaseLabel::Default) {
+                    jumps[i].append(&mut others);
+                }
+                // Each case jumps to the end, without falling into the next.
+                let mut ends = Vec::new();
+                for (i, c) in cases.iter().enumerate() {
+                    let lblcase = code_gen.emit_label();
+                    for &jmp in &jumps[i] {
+                        code_gen.patch_jump(jmp, lblcase);
+                    }
+                    for s in &c.stmts {
+                        s.generate_code(code_gen);
+                    }
+                    if i + 1 < cases.len() {
+                        ends.push(code_gen.emit_jump(OpCode::Goto, lblbegin, Address::null_address()));
+                    }
+                }
+                let lblafter = code_gen.emit_label();
+                for jmp in ends.into_iter().chain(others) {
+                    code_gen.patch_jump(jmp, lblafter);
+                }
+            },
+            StatementKind::Return(ref be) => {
+                let value = match *be {
+                    Some(ref be) => {
+                        let battr = be.generate_code(code_gen);
+                        match code_gen.return_type() {
+                            Some(BasicType::Float) => to_float(code_gen, battr),
+                            _ => to_int(code_gen, battr),
+                        }
+                    },
+                    None => Address::null_address(),
+                };
+                code_gen.emit(OpCode::Ret, Address::null_address(), value, value);
+            },
+            StatementKind::Call(ref name, ref args) => {
+                generate_call(code_gen, name, args);
+            },
+            StatementKind::BlockStmt(ref block) => {
+                block.generate_code(code_gen);
+            },
+            StatementKind::Error => unreachable!("Code generation with syntax errors."),
+        }
+    }
+
+    // Loop, with the name given by its label. Breaks and continues are
+    // patched once the loop is generated.
+    fn generate_loop(&self, code_gen : &mut CodeGenerator, label : Option<String>) {
+        let lblbegin = code_gen.emit_label();
+        match self.kind {
+            StatementKind::While(ref be, ref stmt) => {
+                code_gen.push_loop(label);
                 let battr = be.generate_code(code_gen);
                 let jmp = code_gen.emit_jump(OpCode::JmpZ, lblbegin, battr.place);
                 stmt.generate_code(code_gen);
                 code_gen.emit_jump(OpCode::Goto, lblbegin, Address::null_address());
                 let lblafter = code_gen.emit_label();
-                for id in break_list {
-                    code_gen.patch_jump(id, lblafter);
-                }
-                break_list = Vec::new();
                 code_gen.patch_jump(jmp, lblafter);
+                code_gen.pop_loop(lblbegin, lblafter);
             },
-            &Statement::Break => {
-                let addr = code_gen.emit_jump(OpCode::Goto, lblbegin, Address::null_address());
-                break_list.push(addr);
+            StatementKind::For(ref f) => {
+                // The loop variable has a frame around the whole loop.
+                if let Some(ref d) = f.decl {
+                    code_gen.push_frame();
+                    d.generate_code(code_gen);
+                }
+                if let Some(ref init) = f.init {
+                    init.generate_code(code_gen);
+                }
+                code_gen.push_loop(label);
+                let lblcond = code_gen.emit_label();
+                let jmp = f.cond.as_ref().map(|be| {
+                    let battr = be.generate_code(code_gen);
+                    code_gen.emit_jump(OpCode::JmpZ, lblcond, battr.place)
+                });
+                f.body.generate_code(code_gen);
+                let lblstep = code_gen.emit_label();
+                if let Some(ref step) = f.step {
+                    step.generate_code(code_gen);
+                }
+                code_gen.emit_jump(OpCode::Goto, lblcond, Address::null_address());
+                let lblafter = code_gen.emit_label();
+                if let Some(jmp) = jmp {
+                    code_gen.patch_jump(jmp, lblafter);
+                }
+                code_gen.pop_loop(lblstep, lblafter);
+                if f.decl.is_some() {
+                    code_gen.pop_frame();
+                }
             },
-            &Statement::BlockStmt(ref block) => {
-                block.generate_code(code_gen);    
+            StatementKind::DoWhile(ref stmt, ref be) => {
+                code_gen.push_loop(label);
+                stmt.generate_code(code_gen);
+                let lblcond = code_gen.emit_label();
+                let battr = be.generate_code(code_gen);
+                let jmp = code_gen.emit_jump(OpCode::JmpZ, lblcond, battr.place);
+                code_gen.emit_jump(OpCode::Goto, lblbegin, Address::null_address());
+                let lblafter = code_gen.emit_label();
+                code_gen.patch_jump(jmp, lblafter);
+                code_gen.pop_loop(lblcond, lblafter);
             },
-        }
-        
-        StatementAttributes {
-            break_list : break_list,
+            _ => unreachable!("Label on a statement that is not a loop."),
         }
     }
 }
 
 impl ParseNode for Statement {
-    fn parse(parser : &mut Parser) -> Box<Self> {
-        match parser.lookahead.tag {
+    fn parse(parser : &mut Parser) -> Result<Box<Self>, ParseError> {
+        let start = parser.lookahead.span;
+        let next = if parser.lookahead.tag == Tag::Ide { parser.peek().tag } else { Tag::Eof };
+        let (labeled, call) = (next == Tag::Colon, next == Tag::LParen);
+        let kind = match parser.lookahead.tag {
+            Tag::Ide if labeled => {
+                // stmt -> ID : loop
+                parser.start_node(NodeKind::LabeledStmt);
+                let name = ident(parser.shift_lookahead());
+                parser.shift_lookahead();
+                match parser.lookahead.tag {
+                    Tag::While | Tag::For | Tag::Do => (),
+                    _ => return Err(parser.error("loop")),
+                }
+                StatementKind::Labeled(name, Statement::parse(parser)?)
+            },
+            Tag::Ide if call => {
+                // stmt -> ID ( args );
+                parser.start_node(NodeKind::CallStmt);
+                let (name, args) = parse_call(parser)?;
+                parser.match_lookahead(Tag::SemiColon)?;
+                StatementKind::Call(name, args)
+            },
             Tag::Ide => {
                 // stmt -> loc = bool
-                let l = Loc::parse(parser);
-                parser.match_lookahead(Tag::Assign);
-                let b = BoolExpr::parse(parser);
-                parser.match_lookahead(Tag::SemiColon);
-                Box::new(Statement::Assign(l, b))
+                parser.start_node(NodeKind::AssignStmt);
+                let l = Loc::parse(parser)?;
+                parser.match_lookahead(Tag::Assign)?;
+                let b = BoolExpr::parse(parser)?;
+                parser.match_lookahead(Tag::SemiColon)?;
+                StatementKind::Assign(l, b)
             },
             Tag::If => {
+                parser.start_node(NodeKind::IfStmt);
                 parser.shift_lookahead();
-                parser.match_lookahead(Tag::LParen);
-                let b = BoolExpr::parse(parser);
-                parser.match_lookahead(Tag::RParen);
-                let s = Statement::parse(parser);
+                parser.match_lookahead(Tag::LParen)?;
+                let b = BoolExpr::parse(parser)?;
+                parser.match_lookahead(Tag::RParen)?;
+                let s = Statement::parse(parser)?;
                 if parser.lookahead.tag == Tag::Else {
                     // stmt -> if (bool) stmt else stmt
                     parser.shift_lookahead();
-                    let s2 = Statement::parse(parser);
-                    Box::new(Statement::IfElse(b, s, s2))
+                    let s2 = Statement::parse(parser)?;
+                    StatementKind::IfElse(b, s, s2)
                 } else {
                     // stmt -> if (bool) stmt
-                    Box::new(Statement::If(b, s))
+                    StatementKind::If(b, s)
                 }
             },
             Tag::While => {
                 // stmt -> while (bool) stmt
+                parser.start_node(NodeKind::WhileStmt);
                 parser.shift_lookahead();
-                parser.match_lookahead(Tag::LParen);
-                let b = BoolExpr::parse(parser);
-                parser.match_lookahead(Tag::RParen);
-                let s = Statement::parse(parser);
-                Box::new(Statement::While(b, s))
+                parser.match_lookahead(Tag::LParen)?;
+                let b = BoolExpr::parse(parser)?;
+                parser.match_lookahead(Tag::RParen)?;
+                let s = Statement::parse(parser)?;
+                StatementKind::While(b, s)
             },
-            Tag::Break => {
-                // stmt -> break;
+            Tag::For => {
+                // stmt -> for (init; cond; step) stmt
+                parser.start_node(NodeKind::ForStmt);
                 parser.shift_lookahead();
-                parser.match_lookahead(Tag::SemiColon);
-                Box::new(Statement::Break)
+                parser.match_lookahead(Tag::LParen)?;
+                let (decl, init) = match parser.lookahead.tag {
+                    // init -> type ID = bool
+                    Tag::Type => {
+                        let (d, init) = for_decl(parser)?;
+                        (Some(d), Some(init))
+                    },
+                    // init -> loc = bool
+                    Tag::Ide => (None, Some(assign(parser)?)),
+                    _ => (None, None),
+                };
+                parser.match_lookahead(Tag::SemiColon)?;
+                let cond = if parser.lookahead.tag != Tag::SemiColon {
+                    Some(BoolExpr::parse(parser)?)
+                } else { None };
+                parser.match_lookahead(Tag::SemiColon)?;
+                let step = if parser.lookahead.tag != Tag::RParen {
+                    Some(assign(parser)?)
+                } else { None };
+                parser.match_lookahead(Tag::RParen)?;
+                let body = Statement::parse(parser)?;
+                StatementKind::For(Box::new(ForLoop {
+                    decl,
+                    init,
+                    cond,
+                    step,
+                    body,
+                }))
+            },
+            Tag::Do => {
+                // stmt -> do stmt while (bool);
+                parser.start_node(NodeKind::DoWhileStmt);
+                parser.shift_lookahead();
+                let s = Statement::parse(parser)?;
+                parser.match_lookahead(Tag::While)?;
+                parser.match_lookahead(Tag::LParen)?;
+                let b = BoolExpr::parse(parser)?;
+                parser.match_lookahead(Tag::RParen)?;
+                parser.match_lookahead(Tag::SemiColon)?;
+                StatementKind::DoWhile(s, b)
+            },
+            Tag::Switch => {
+                // stmt -> switch (bool) { cases }
+                parser.start_node(NodeKind::SwitchStmt);
+                parser.shift_lookahead();
+                parser.match_lookahead(Tag::LParen)?;
+                let b = BoolExpr::parse(parser)?;
+                parser.match_lookahead(Tag::RParen)?;
+                parser.match_lookahead(Tag::OpenBlock)?;
+                let mut cases = Vec::new();
+                while parser.lookahead.tag == Tag::Case || parser.lookahead.tag == Tag::Default {
+                    cases.push(case(parser)?);
+                }
+                parser.match_lookahead(Tag::CloseBlock)?;
+                StatementKind::Switch(b, cases)
             },
-            Tag::LArrParen => {
+            Tag::Return => {
+                // stmt -> return; | return bool;
+                parser.start_node(NodeKind::ReturnStmt);
+                parser.shift_lookahead();
+                let value = if parser.lookahead.tag != Tag::SemiColon {
+                    Some(BoolExpr::parse(parser)?)
+                } else { None };
+                parser.match_lookahead(Tag::SemiColon)?;
+                StatementKind::Return(value)
+            },
+            Tag::Break | Tag::Continue => {
+                // stmt -> break; | break ID; | continue; | continue ID;
+                let brk = parser.lookahead.tag == Tag::Break;
+                parser.start_node(if brk { NodeKind::BreakStmt } else { NodeKind::ContinueStmt });
+                parser.shift_lookahead();
+                let label = if parser.lookahead.tag == Tag::Ide {
+                    Some(ident(parser.shift_lookahead()))
+                } else { None };
+                parser.match_lookahead(Tag::SemiColon)?;
+                if brk { StatementKind::Break(label) } else { StatementKind::Continue(label) }
+            },
+            Tag::OpenBlock => {
                 // stmt -> block
-                Box::new(Statement::BlockStmt(Block::parse(parser)))
+                parser.start_node(NodeKind::BlockStmt);
+                StatementKind::BlockStmt(Block::parse(parser)?)
             }
-            _ => panic!("Expected a valid statement.")
-        }
+            _ => return Err(parser.error("statement")),
+        };
+        parser.finish_node();
+        Ok(Statement::new(kind, parser.span_from(start)))
     }    
 }
+
+// case label : stmts | default : stmts
+fn case(parser : &mut Parser) -> Result<Case, ParseError> {
+    let start = parser.lookahead.span;
+    parser.start_node(NodeKind::Case);
+    let label = if parser.shift_lookahead().tag == Tag::Default {
+        CaseLabel::Default
+    } else {
+        // label -> num | - num | true | false
+        let label_start = parser.lookahead.span;
+        let minus = parser.lookahead.tag == Tag::NumOp && parser.lookahead.info == TokenInfo::Sub;
+        if minus {
+            parser.match_lookahead(Tag::NumOp)?;
+        }
+        match parser.lookahead.tag {
+            Tag::Num => {
+                let tok = parser.shift_lookahead();
+                let x = match tok.info {
+                    TokenInfo::Num(x) => x as i64,
+                    _ => unreachable!("Wrong token info for num."),
+                };
+                let x = if minus { x.checked_neg() } else { Some(x) };
+                match x {
+                    Some(x) if x >= i32::MIN as i64 && x <= i32::MAX as i64 => CaseLabel::Int(x as i32),
+                    _ => {
+                        let span = parser.span_from(label_start);
+                        return Err(ParseError::invalid("case label out of range", tok, span));
+                    }
+                }
+            },
+            Tag::True if !minus => { parser.shift_lookahead(); CaseLabel::Bool(true) },
+            Tag::False if !minus => { parser.shift_lookahead(); CaseLabel::Bool(false) },
+            _ => return Err(parser.error("case label")),
+        }
+    };
+    parser.match_lookahead(Tag::Colon)?;
+    let span = parser.span_from(start);
+    let mut stmts = Vec::new();
+    loop {
+        match parser.lookahead.tag {
+            Tag::Case | Tag::Default | Tag::CloseBlock | Tag::Eof => break,
+            _ => stmts.push(Statement::parse(parser)?),
+        }
+    }
+    parser.finish_node();
+    Ok(Case {
+        label,
+        stmts,
+        span,
+    })
+}
+
+// loc = bool, without the semicolon, in the header of a for.
+fn assign(parser : &mut Parser) -> Result<Box<Statement>, ParseError> {
+    let start = parser.lookahead.span;
+    parser.start_node(NodeKind::AssignStmt);
+    let l = Loc::parse(parser)?;
+    parser.match_lookahead(Tag::Assign)?;
+    let b = BoolExpr::parse(parser)?;
+    parser.finish_node();
+    Ok(Statement::new(StatementKind::Assign(l, b), parser.span_from(start)))
+}
+
+// type ID = bool: the declaration of the loop variable and its assignment.
+fn for_decl(parser : &mut Parser) -> Result<(Box<Decl>, Box<Statement>), ParseError> {
+    let start = parser.lookahead.span;
+    parser.start_node(NodeKind::Decl);
+    let tid = Type::parse(parser)?;
+    let name_span = parser.lookahead.span;
+    let name = ident(parser.match_lookahead(Tag::Ide)?);
+    let decl = Box::new(Decl {
+        type_id : tid,
+        id : Box::new(name.clone()),
+        span : parser.span_from(start),
+    });
+    parser.match_lookahead(Tag::Assign)?;
+    let b = BoolExpr::parse(parser)?;
+    parser.finish_node();
+    let l = Loc::new(LocKind::Ide(name), name_span);
+    let init = Statement::new(StatementKind::Assign(l, b), parser.span_from(name_span));
+    Ok((decl, init))
+}
+
+fn ident(tok : Token) -> String {
+    if let TokenInfo::Ide(s) = tok.info {
+        s
+    } else { unreachable!("Wrong TokenInfo for Ide.") }
+}
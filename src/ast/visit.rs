@@ -0,0 +1,328 @@
+/*
+    Traversal of the tree. A pass implements the methods for the nodes it
+    cares about and calls the walk function to keep going into the children.
+    Visitor reads the tree, MutVisitor can change it in place.
+*/
+use ast::statement::{Program, Item, Function, Block, Decl, Statement, StatementKind};
+use ast::expression::{BoolExpr, BoolExprKind, NumExpr, NumExprKind, Loc, LocKind};
+
+pub trait Visitor {
+    fn visit_program(&mut self, p : &Program) {
+        walk_program(self, p);
+    }
+
+    fn visit_function(&mut self, f : &Function) {
+        walk_function(self, f);
+    }
+
+    fn visit_block(&mut self, b : &Block) {
+        walk_block(self, b);
+    }
+
+    fn visit_decl(&mut self, _d : &Decl) {}
+
+    fn visit_statement(&mut self, s : &Statement) {
+        walk_statement(self, s);
+    }
+
+    fn visit_bool_expr(&mut self, e : &BoolExpr) {
+        walk_bool_expr(self, e);
+    }
+
+    fn visit_num_expr(&mut self, e : &NumExpr) {
+        walk_num_expr(self, e);
+    }
+
+    fn visit_loc(&mut self, l : &Loc) {
+        walk_loc(self, l);
+    }
+}
+
+pub fn walk_program<V : Visitor + ?Sized>(v : &mut V, p : &Program) {
+    for item in &p.items {
+        match *item {
+            Item::Global(ref d) => v.visit_decl(d),
+            Item::Const(ref c) => v.visit_bool_expr(&c.value),
+            Item::Function(ref f) => v.visit_function(f),
+        }
+    }
+    if let Some(ref b) = p.block {
+        v.visit_block(b);
+    }
+}
+
+// Parameters are declarations of the body.
+pub fn walk_function<V : Visitor + ?Sized>(v : &mut V, f : &Function) {
+    for d in &f.params {
+        v.visit_decl(d);
+    }
+    v.visit_block(&f.body);
+}
+
+// Declarations come before the statements, as in code generation.
+pub fn walk_block<V : Visitor + ?Sized>(v : &mut V, b : &Block) {
+    for d in &b.decls {
+        v.visit_decl(d);
+    }
+    for s in &b.stmts {
+        v.visit_statement(s);
+    }
+}
+
+pub fn walk_statement<V : Visitor + ?Sized>(v : &mut V, s : &Statement) {
+    match s.kind {
+        StatementKind::Assign(ref l, ref e) => {
+            v.visit_loc(l);
+            v.visit_bool_expr(e);
+        },
+        StatementKind::If(ref e, ref s1) | StatementKind::While(ref e, ref s1) => {
+            v.visit_bool_expr(e);
+            v.visit_statement(s1);
+        },
+        StatementKind::IfElse(ref e, ref s1, ref s2) => {
+            v.visit_bool_expr(e);
+            v.visit_statement(s1);
+            v.visit_statement(s2);
+        },
+        StatementKind::For(ref f) => {
+            if let Some(ref d) = f.decl {
+                v.visit_decl(d);
+            }
+            if let Some(ref init) = f.init {
+                v.visit_statement(init);
+            }
+            if let Some(ref e) = f.cond {
+                v.visit_bool_expr(e);
+            }
+            if let Some(ref step) = f.step {
+                v.visit_statement(step);
+            }
+            v.visit_statement(&f.body);
+        },
+        StatementKind::DoWhile(ref s1, ref e) => {
+            v.visit_statement(s1);
+            v.visit_bool_expr(e);
+        },
+        StatementKind::Switch(ref e, ref cases) => {
+            v.visit_bool_expr(e);
+            for c in cases {
+                for s1 in &c.stmts {
+                    v.visit_statement(s1);
+                }
+            }
+        },
+        StatementKind::Labeled(_, ref s1) => v.visit_statement(s1),
+        StatementKind::Return(Some(ref e)) => v.visit_bool_expr(e),
+        StatementKind::Call(_, ref args) => {
+            for e in args {
+                v.visit_bool_expr(e);
+            }
+        },
+        StatementKind::BlockStmt(ref b) => v.visit_block(b),
+        StatementKind::Return(None) | StatementKind::Break(_) | StatementKind::Continue(_) |
+        StatementKind::Error => (),
+    }
+}
+
+pub fn walk_bool_expr<V : Visitor + ?Sized>(v : &mut V, e : &BoolExpr) {
+    match e.kind {
+        BoolExprKind::Or(ref e1, ref e2) | BoolExprKind::And(ref e1, ref e2) |
+        BoolExprKind::Eq(ref e1, ref e2) | BoolExprKind::Neq(ref e1, ref e2) => {
+            v.visit_bool_expr(e1);
+            v.visit_bool_expr(e2);
+        },
+        BoolExprKind::Relop(_, ref e1, ref e2) => {
+            v.visit_num_expr(e1);
+            v.visit_num_expr(e2);
+        },
+        BoolExprKind::NumExpr(ref e1) => v.visit_num_expr(e1),
+    }
+}
+
+pub fn walk_num_expr<V : Visitor + ?Sized>(v : &mut V, e : &NumExpr) {
+    match e.kind {
+        NumExprKind::Add(ref e1, ref e2) | NumExprKind::Sub(ref e1, ref e2) |
+        NumExprKind::Mul(ref e1, ref e2) | NumExprKind::Div(ref e1, ref e2) => {
+            v.visit_num_expr(e1);
+            v.visit_num_expr(e2);
+        },
+        NumExprKind::Not(ref e1) | NumExprKind::Minus(ref e1) => v.visit_num_expr(e1),
+        NumExprKind::Expr(ref b) => v.visit_bool_expr(b),
+        NumExprKind::Loc(ref l) => v.visit_loc(l),
+        NumExprKind::Call(_, ref args) => {
+            for e in args {
+                v.visit_bool_expr(e);
+            }
+        },
+        NumExprKind::Num(_) | NumExprKind::Real(_) |
+        NumExprKind::True | NumExprKind::False => (),
+    }
+}
+
+pub fn walk_loc<V : Visitor + ?Sized>(v : &mut V, l : &Loc) {
+    if let LocKind::Index(_, ref indices) = l.kind {
+        for e in indices {
+            v.visit_bool_expr(e);
+        }
+    }
+}
+
+pub trait MutVisitor {
+    fn visit_program(&mut self, p : &mut Program) {
+        walk_program_mut(self, p);
+    }
+
+    fn visit_function(&mut self, f : &mut Function) {
+        walk_function_mut(self, f);
+    }
+
+    fn visit_block(&mut self, b : &mut Block) {
+        walk_block_mut(self, b);
+    }
+
+    fn visit_decl(&mut self, _d : &mut Decl) {}
+
+    fn visit_statement(&mut self, s : &mut Statement) {
+        walk_statement_mut(self, s);
+    }
+
+    fn visit_bool_expr(&mut self, e : &mut BoolExpr) {
+        walk_bool_expr_mut(self, e);
+    }
+
+    fn visit_num_expr(&mut self, e : &mut NumExpr) {
+        walk_num_expr_mut(self, e);
+    }
+
+    fn visit_loc(&mut self, l : &mut Loc) {
+        walk_loc_mut(self, l);
+    }
+}
+
+pub fn walk_program_mut<V : MutVisitor + ?Sized>(v : &mut V, p : &mut Program) {
+    for item in &mut p.items {
+        match *item {
+            Item::Global(ref mut d) => v.visit_decl(d),
+            Item::Const(ref mut c) => v.visit_bool_expr(&mut c.value),
+            Item::Function(ref mut f) => v.visit_function(f),
+        }
+    }
+    if let Some(ref mut b) = p.block {
+        v.visit_block(b);
+    }
+}
+
+// Parameters are declarations of the body.
+pub fn walk_function_mut<V : MutVisitor + ?Sized>(v : &mut V, f : &mut Function) {
+    for d in &mut f.params {
+        v.visit_decl(d);
+    }
+    v.visit_block(&mut f.body);
+}
+
+pub fn walk_block_mut<V : MutVisitor + ?Sized>(v : &mut V, b : &mut Block) {
+    for d in &mut b.decls {
+        v.visit_decl(d);
+    }
+    for s in &mut b.stmts {
+        v.visit_statement(s);
+    }
+}
+
+pub fn walk_statement_mut<V : MutVisitor + ?Sized>(v : &mut V, s : &mut Statement) {
+    match s.kind {
+        StatementKind::Assign(ref mut l, ref mut e) => {
+            v.visit_loc(l);
+            v.visit_bool_expr(e);
+        },
+        StatementKind::If(ref mut e, ref mut s1) | StatementKind::While(ref mut e, ref mut s1) => {
+            v.visit_bool_expr(e);
+            v.visit_statement(s1);
+        },
+        StatementKind::IfElse(ref mut e, ref mut s1, ref mut s2) => {
+            v.visit_bool_expr(e);
+            v.visit_statement(s1);
+            v.visit_statement(s2);
+        },
+        StatementKind::For(ref mut f) => {
+            if let Some(ref mut d) = f.decl {
+                v.visit_decl(d);
+            }
+            if let Some(ref mut init) = f.init {
+                v.visit_statement(init);
+            }
+            if let Some(ref mut e) = f.cond {
+                v.visit_bool_expr(e);
+            }
+            if let Some(ref mut step) = f.step {
+                v.visit_statement(step);
+            }
+            v.visit_statement(&mut f.body);
+        },
+        StatementKind::DoWhile(ref mut s1, ref mut e) => {
+            v.visit_statement(s1);
+            v.visit_bool_expr(e);
+        },
+        StatementKind::Switch(ref mut e, ref mut cases) => {
+            v.visit_bool_expr(e);
+            for c in cases {
+                for s1 in &mut c.stmts {
+                    v.visit_statement(s1);
+                }
+            }
+        },
+        StatementKind::Labeled(_, ref mut s1) => v.visit_statement(s1),
+        StatementKind::Return(Some(ref mut e)) => v.visit_bool_expr(e),
+        StatementKind::Call(_, ref mut args) => {
+            for e in args {
+                v.visit_bool_expr(e);
+            }
+        },
+        StatementKind::BlockStmt(ref mut b) => v.visit_block(b),
+        StatementKind::Return(None) | StatementKind::Break(_) | StatementKind::Continue(_) |
+        StatementKind::Error => (),
+    }
+}
+
+pub fn walk_bool_expr_mut<V : MutVisitor + ?Sized>(v : &mut V, e : &mut BoolExpr) {
+    match e.kind {
+        BoolExprKind::Or(ref mut e1, ref mut e2) | BoolExprKind::And(ref mut e1, ref mut e2) |
+        BoolExprKind::Eq(ref mut e1, ref mut e2) | BoolExprKind::Neq(ref mut e1, ref mut e2) => {
+            v.visit_bool_expr(e1);
+            v.visit_bool_expr(e2);
+        },
+        BoolExprKind::Relop(_, ref mut e1, ref mut e2) => {
+            v.visit_num_expr(e1);
+            v.visit_num_expr(e2);
+        },
+        BoolExprKind::NumExpr(ref mut e1) => v.visit_num_expr(e1),
+    }
+}
+
+pub fn walk_num_expr_mut<V : MutVisitor + ?Sized>(v : &mut V, e : &mut NumExpr) {
+    match e.kind {
+        NumExprKind::Add(ref mut e1, ref mut e2) | NumExprKind::Sub(ref mut e1, ref mut e2) |
+        NumExprKind::Mul(ref mut e1, ref mut e2) | NumExprKind::Div(ref mut e1, ref mut e2) => {
+            v.visit_num_expr(e1);
+            v.visit_num_expr(e2);
+        },
+        NumExprKind::Not(ref mut e1) | NumExprKind::Minus(ref mut e1) => v.visit_num_expr(e1),
+        NumExprKind::Expr(ref mut b) => v.visit_bool_expr(b),
+        NumExprKind::Loc(ref mut l) => v.visit_loc(l),
+        NumExprKind::Call(_, ref mut args) => {
+            for e in args {
+                v.visit_bool_expr(e);
+            }
+        },
+        NumExprKind::Num(_) | NumExprKind::Real(_) |
+        NumExprKind::True | NumExprKind::False => (),
+    }
+}
+
+pub fn walk_loc_mut<V : MutVisitor + ?Sized>(v : &mut V, l : &mut Loc) {
+    if let LocKind::Index(_, ref mut indices) = l.kind {
+        for e in indices {
+            v.visit_bool_expr(e);
+        }
+    }
+}
@@ -0,0 +1,134 @@
+/*
+    Control flow graph of the three-address code: basic blocks of
+    instructions and the edges given by jumps and fallthrough.
+*/
+use code_generator::{IntermediateRepresentation, OpCode};
+
+// Instructions from start to end, excluded, as indices in the code.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct BasicBlock {
+    pub start : usize,
+    pub end   : usize,
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum EdgeKind {
+    Fallthrough,
+    Goto,
+    IfZero,     // JmpZ taken.
+    IfNotZero,  // JmpZ not taken.
+    Table(i32), // JmpInd to the entry with this index.
+    Return,     // Ret, out of the function to the exit.
+}
+
+// Edge between blocks. A target equal to the number of blocks is the exit.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct Edge {
+    pub from : usize,
+    pub to   : usize,
+    pub kind : EdgeKind,
+}
+
+pub struct Cfg {
+    pub blocks : Vec<BasicBlock>,
+    pub edges  : Vec<Edge>,
+}
+
+impl Cfg {
+    pub fn new(code : &IntermediateRepresentation) -> Self {
+        let instrs = &code.instructions;
+        let n = instrs.len();
+        // Labels are instruction ids, starting from 1.
+        let target = |i : usize| (instrs[i].res.place - 1) as usize;
+        let is_jump = |i : usize| matches!(instrs[i].op, OpCode::Goto | OpCode::JmpZ | OpCode::JmpInd);
+
+        // A block starts at the beginning, at each jump target and after
+        // each jump or return. Calls come back, so they do not end blocks.
+        let mut leader = vec![false; n + 1];
+        leader[0] = true;
+        for i in 0..n {
+            if instrs[i].op == OpCode::Ret {
+                leader[i + 1] = true;
+            } else if is_jump(i) {
+                leader[i + 1] = true;
+                if target(i) < n {
+                    leader[target(i)] = true;
+                }
+            }
+        }
+        let mut blocks = Vec::new();
+        let mut block_of = vec![0; n + 1];
+        for i in 0..n {
+            if leader[i] {
+                blocks.push(BasicBlock { start : i, end : i });
+            }
+            let b = blocks.len() - 1;
+            blocks[b].end = i + 1;
+            block_of[i] = b;
+        }
+        // Jumps past the last instruction go to the exit.
+        block_of[n] = blocks.len();
+        let block_at = |i : usize| if i < n { block_of[i] } else { block_of[n] };
+
+        let mut edges = Vec::new();
+        for (b, block) in blocks.iter().enumerate() {
+            let last = block.end - 1;
+            let next = block_at(block.end);
+            let edge = |to, kind| Edge { from : b, to, kind };
+            match instrs[last].op {
+                OpCode::Goto => edges.push(edge(block_at(target(last)), EdgeKind::Goto)),
+                OpCode::JmpZ => {
+                    edges.push(edge(block_at(target(last)), EdgeKind::IfZero));
+                    edges.push(edge(next, EdgeKind::IfNotZero));
+                },
+                // The entries of the table are gotos, so each one is a block.
+                OpCode::JmpInd => {
+                    for k in 0..instrs[last].y.place {
+                        edges.push(edge(block_at(target(last) + k as usize), EdgeKind::Table(k)));
+                    }
+                },
+                OpCode::Ret => edges.push(edge(blocks.len(), EdgeKind::Return)),
+                _ => edges.push(edge(next, EdgeKind::Fallthrough)),
+            }
+        }
+        Cfg {
+            blocks,
+            edges,
+        }
+    }
+
+    // Index of the exit node, after the last block.
+    pub fn exit(&self) -> usize {
+        self.blocks.len()
+    }
+
+    // Graphviz graph with the instructions of each block.
+    pub fn to_dot(&self, code : &IntermediateRepresentation) -> String {
+        let mut out = String::from("digraph cfg {\n");
+        out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        out.push_str("    entry [shape=oval];\n");
+        out.push_str("    exit [shape=oval];\n");
+        for (b, block) in self.blocks.iter().enumerate() {
+            let mut label = String::new();
+            for instr in &code.instructions[block.start..block.end] {
+                label.push_str(&format!("{}\\l", instr));
+            }
+            out.push_str(&format!("    b{} [label=\"{}\"];\n", b, label));
+        }
+        let name = |b : usize| if b == self.exit() { String::from("exit") } else { format!("b{}", b) };
+        out.push_str(&format!("    entry -> {};\n", name(0)));
+        for e in &self.edges {
+            let attrs = match e.kind {
+                EdgeKind::Fallthrough => String::new(),
+                EdgeKind::Goto => String::from(" [label=\"goto\"]"),
+                EdgeKind::IfZero => String::from(" [label=\"zero\"]"),
+                EdgeKind::IfNotZero => String::from(" [label=\"not zero\"]"),
+                EdgeKind::Table(k) => format!(" [label=\"{}\"]", k),
+                EdgeKind::Return => String::from(" [label=\"ret\"]"),
+            };
+            out.push_str(&format!("    {} -> {}{};\n", name(e.from), name(e.to), attrs));
+        }
+        out.push_str("}\n");
+        out
+    }
+}
@@ -1,6 +1,17 @@
 use parser::Parser;
 use symbol_table::SymbolTable;
+use ast::statement::BasicType;
+use diagnostics::Diagnostic;
+use resolver;
+use typeck;
+use uninit;
+use lint;
+use lint::LintLevels;
+use std;
+use std::collections::HashMap;
+use std::fmt;
 
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum OpCode {
     Mov,
     // Boolean operators.
@@ -15,9 +26,22 @@ pub enum OpCode {
     Mul,
     Div,
     Minus,
+    // Float operators.
+    FAdd,
+    FSub,
+    FMul,
+    FDiv,
+    FMinus,
+    IntToFloat,
+    FloatToInt,
     // Jump.
     Goto,
     JmpZ,
+    JmpInd,     // Jump to the label plus x, into a table of y gotos.
+    // Calls.
+    Param,      // Pass x to the next call.
+    Call,       // Call the function at x with the last y params, its value in res.
+    Ret,        // Return x, or nothing if it is the null address.
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -25,7 +49,9 @@ pub enum AddressMode {
     Register,       // Register containing a value.
     FramePointer,   // Register containing an address to stack variable.
     Constant,       // Constant value not in a register.
+    FloatConstant,  // Index of a constant inside the float constant table.
     Label,          // ID of an instruction for JUMP and CALL.
+    Static,         // Index in the static storage of globals and constants.
 }
 
 #[derive(PartialEq, Debug, Clone, Copy)]
@@ -55,6 +81,22 @@ impl Address {
     }
 }
 
+// Registers are t1, t2..., pointers to stack variables p1, p2..., entries of
+// the float constant table f0, f1..., labels L1, L2... and static storage
+// s0, s1...
+impl fmt::Display for Address {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match self.mode {
+            AddressMode::Register => write!(f, "t{}", self.place),
+            AddressMode::FramePointer => write!(f, "p{}", self.place),
+            AddressMode::Constant => write!(f, "{}", self.place),
+            AddressMode::FloatConstant => write!(f, "f{}", self.place),
+            AddressMode::Label => write!(f, "L{}", self.place),
+            AddressMode::Static => write!(f, "s{}", self.place),
+        }
+    }
+}
+
 pub struct AddressCode {
         id  : i32,
     pub op  : OpCode,
@@ -63,14 +105,48 @@ pub struct AddressCode {
     pub y   : Address,
 }
 
+// One instruction per line, prefixed by its id: `3: t2 = add t1, 4`.
+impl fmt::Display for AddressCode {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let op = format!("{:?}", self.op).to_lowercase();
+        write!(f, "{}: ", self.id)?;
+        match self.op {
+            OpCode::Goto => write!(f, "goto {}", self.res),
+            OpCode::JmpZ => write!(f, "jmpz {}, {}", self.x, self.res),
+            OpCode::JmpInd => write!(f, "jmpind {}, {}, {}", self.x, self.res, self.y),
+            OpCode::Param => write!(f, "param {}", self.x),
+            OpCode::Call => write!(f, "{} = call {}, {}", self.res, self.x, self.y),
+            OpCode::Ret if self.x == Address::null_address() => write!(f, "ret"),
+            OpCode::Ret => write!(f, "ret {}", self.x),
+            OpCode::Mov => write!(f, "{} = {}", self.res, self.x),
+            OpCode::Not | OpCode::IsPos | OpCode::IsNeg | OpCode::Minus |
+            OpCode::FMinus | OpCode::IntToFloat | OpCode::FloatToInt => {
+                write!(f, "{} = {} {}", self.res, op, self.x)
+            },
+            _ => write!(f, "{} = {} {}, {}", self.res, op, self.x, self.y),
+        }
+    }
+}
+
+// Declared variable, kept to inspect the result of the execution.
+pub struct Variable {
+    pub name       : String,
+    pub address    : Address,
+    pub basic_type : BasicType,
+}
+
 pub struct IntermediateRepresentation {
     pub instructions : Vec<AddressCode>,
+    pub float_constants : Vec<f64>,
+    pub variables : Vec<Variable>,
 }
 
 impl IntermediateRepresentation {
     fn new() -> Self {
         IntermediateRepresentation {
             instructions : Vec::new(),
+            float_constants : Vec::new(),
+            variables : Vec::new(),
         }
     }
     
@@ -84,11 +160,34 @@ impl IntermediateRepresentation {
     }
 }
 
+// Loop being generated, with the jumps out of it to patch once its end is
+// known.
+pub struct LoopContext {
+    pub label     : Option<String>,
+    pub depth     : usize,          // Frames open outside the loop.
+    pub breaks    : Vec<usize>,
+    pub continues : Vec<usize>,
+}
+
+// Signature of a function and its entry, once generated.
+struct FunctionInfo {
+    params : Vec<BasicType>,
+    ret    : Option<BasicType>,
+    entry  : Option<Label>,
+}
+
 pub struct CodeGenerator {
     pub sym_table   : SymbolTable, 
     parser          : Parser,    
     pub code            : IntermediateRepresentation,
+    pub diagnostics : Vec<Diagnostic>,
+    pub lints       : LintLevels,
+    loops           : Vec<LoopContext>,
+    functions       : HashMap<String, FunctionInfo>,
+    function        : Option<String>,           // Function being generated.
+    calls           : Vec<(usize, String)>,     // Calls to patch with the entry.
     temp_num        : i32,
+    static_num      : i32,
     instr_num       : i32,
     
     stack_pointer   : Address,
@@ -100,7 +199,14 @@ impl CodeGenerator {
             parser    : parser,
             sym_table : SymbolTable::new(),
             code      : IntermediateRepresentation::new(),
+            diagnostics : Vec::new(),
+            lints     : LintLevels::new(),
+            loops     : Vec::new(),
+            functions : HashMap::new(),
+            function  : None,
+            calls     : Vec::new(),
             temp_num  : 0,
+            static_num : 0,
             instr_num : 0,
             
             stack_pointer : Address::null_address(),
@@ -135,6 +241,107 @@ impl CodeGenerator {
         self.emit(OpCode::Sub, stp, stp, Address::new_constant(w as i32));
     }
     
+    pub fn push_loop(&mut self, label : Option<String>) {
+        let depth = self.sym_table.depth();
+        self.loops.push(LoopContext {
+            label,
+            depth,
+            breaks : Vec::new(),
+            continues : Vec::new(),
+        });
+    }
+
+    // Send the continues of the innermost loop to the next iteration and its
+    // breaks after it.
+    pub fn pop_loop(&mut self, lblcontinue : Label, lblbreak : Label) {
+        let l = self.loops.pop().unwrap();
+        for id in l.continues {
+            self.patch_jump(id, lblcontinue);
+        }
+        for id in l.breaks {
+            self.patch_jump(id, lblbreak);
+        }
+    }
+
+    // Loop with the given label, or the innermost one. The resolver has
+    // checked that it exists.
+    pub fn innermost_loop(&mut self, label : &Option<String>) -> &mut LoopContext {
+        self.loops.iter_mut().rev()
+            .find(|l| label.is_none() || l.label == *label)
+            .expect("Break or continue outside of a loop.")
+    }
+
+    // Jump out of the blocks opened inside the loop, to be patched.
+    pub fn emit_loop_exit(&mut self, label : &Option<String>) -> usize {
+        let depth = self.innermost_loop(label).depth;
+        let stp = self.stack_pointer;
+        for i in (depth..self.sym_table.depth()).rev() {
+            let w = self.sym_table.frame_width(i);
+            self.emit(OpCode::Sub, stp, stp, Address::new_constant(w as i32));
+        }
+        let lbl = self.emit_label();
+        self.emit_jump(OpCode::Goto, lbl, Address::null_address())
+    }
+
+    pub fn declare_function(&mut self, name : &str, params : Vec<BasicType>, ret : Option<BasicType>) {
+        self.functions.insert(String::from(name), FunctionInfo {
+            params,
+            ret,
+            entry  : None,
+        });
+    }
+
+    pub fn function_type(&self, name : &str) -> (Vec<BasicType>, Option<BasicType>) {
+        let f = &self.functions[name];
+        (f.params.clone(), f.ret)
+    }
+
+    // Start the code of a function at the next instruction. Each activation
+    // has registers of its own, numbered again from the stack pointer: the
+    // parameters, declared first, are in t2, t3..., where the call puts the
+    // arguments. Returns the register count of the caller to restore.
+    pub fn enter_function(&mut self, name : &str) -> i32 {
+        let entry = self.emit_label();
+        self.functions.get_mut(name).unwrap().entry = Some(entry);
+        self.function = Some(String::from(name));
+        std::mem::replace(&mut self.temp_num, self.stack_pointer.place)
+    }
+
+    pub fn leave_function(&mut self, temp_num : i32) {
+        self.function = None;
+        self.temp_num = temp_num;
+    }
+
+    pub fn in_function(&self) -> bool {
+        self.function.is_some()
+    }
+
+    // Type of the value returned by the function being generated.
+    pub fn return_type(&self) -> Option<BasicType> {
+        self.function.as_ref().and_then(|f| self.functions[f].ret)
+    }
+
+    // The entry of the function may not be known yet: calls are patched
+    // once all the code is generated.
+    pub fn emit_call(&mut self, name : &str, args : Vec<Address>) -> Address {
+        let n = args.len();
+        for a in args {
+            self.emit(OpCode::Param, Address::null_address(), a, a);
+        }
+        let res = self.new_temp();
+        let entry = Address { mode : AddressMode::Label, place : 0 };
+        self.emit(OpCode::Call, res, entry, Address::new_constant(n as i32));
+        self.calls.push((self.code.get_last(), String::from(name)));
+        res
+    }
+
+    pub fn patch_calls(&mut self) {
+        for (instr, name) in std::mem::take(&mut self.calls) {
+            let entry = self.functions[&name].entry.expect("Call of a function never generated.");
+            self.code.instructions[instr].x.place = entry.place;
+        }
+    }
+
     pub fn emit_label(&self) -> Label {
         Label {
             place : self.instr_num + 1,
@@ -159,6 +366,17 @@ impl CodeGenerator {
         self.code.get_last()
     }
     
+    // Jump to the entry selected by index in the table of len gotos that
+    // follows.
+    pub fn emit_jump_table(&mut self, index : Address, len : usize) -> usize {
+        let lbltable = Label {
+            place : self.instr_num + 2,
+        };
+        let jmp = self.emit_jump(OpCode::JmpInd, lbltable, index);
+        self.code.instructions[jmp].y = Address::new_constant(len as i32);
+        jmp
+    }
+
     pub fn patch_jump(&mut self, addr : usize, lbl : Label) {
         self.code.instructions[addr].res.place = lbl.place;
     }
@@ -171,6 +389,23 @@ impl CodeGenerator {
         }
     }
     
+    // Storage of a global, shared by all the activations.
+    pub fn new_static(&mut self) -> Address {
+        self.static_num += 1;
+        Address {
+            mode  : AddressMode::Static,
+            place : self.static_num - 1,
+        }
+    }
+
+    pub fn new_float_constant(&mut self, x : f64) -> Address {
+        self.code.float_constants.push(x);
+        Address {
+            mode  : AddressMode::FloatConstant,
+            place : (self.code.float_constants.len() - 1) as i32,
+        }
+    }
+    
     pub fn new_pointer(&mut self) -> Address {
         self.temp_num += 1;
         Address {
@@ -179,9 +414,22 @@ impl CodeGenerator {
         }
     }
     
-    pub fn generate_code(&mut self) {
-        self.parser.parse();
-        let root = self.parser.get_root();
+    pub fn error(&mut self, diag : Diagnostic) {
+        self.diagnostics.push(diag);
+    }
+    
+    pub fn generate_code(&mut self) -> Result<(), Vec<Diagnostic>> {
+        let mut root = self.parser.parse_program()?;
+        let decls = resolver::resolve(&mut root)?;
+        typeck::check(&root, &decls)?;
+        let mut lints = uninit::check(&root, &decls);
+        lints.append(&mut lint::check(&root, &decls));
+        self.diagnostics.append(&mut self.lints.apply(lints));
         root.generate_code(self);        
+        if self.diagnostics.iter().any(|d| d.is_error()) {
+            Err(std::mem::take(&mut self.diagnostics))
+        } else {
+            Ok(())
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,264 @@
+/*
+    Concrete syntax tree: the parse tree with every token of the source and
+    the trivia (spaces, comments, bad characters) in front of it. Printing
+    the tree gives back the source text unchanged.
+*/
+use lexer::{Scanner, Token, Tag};
+use parser::{Parser, ParseError};
+use std::fmt;
+use std::mem;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum NodeKind {
+    File,       // Program followed by the end of file.
+    Program,
+    ConstDecl,
+    Function,   // Header with the parameters, and the body.
+    Param,
+    Block,
+    Decl,
+    Type,
+    AssignStmt,
+    IfStmt,     // With or without the else branch.
+    WhileStmt,
+    ForStmt,
+    DoWhileStmt,
+    SwitchStmt,
+    Case,       // Label of a case and its statements.
+    BreakStmt,
+    ContinueStmt,
+    LabeledStmt,
+    ReturnStmt,
+    CallStmt,
+    BlockStmt,
+    Error,      // Tokens skipped while recovering from a syntax error.
+    BinaryExpr,
+    UnaryExpr,
+    ParenExpr,
+    Literal,
+    Call,       // Name and arguments, in an expression or a statement.
+    Loc,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum TriviaKind {
+    Whitespace,
+    Comment,
+    Skipped,    // Characters the scanner could not make a token of.
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Trivia {
+    pub kind : TriviaKind,
+    pub text : String,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct SyntaxToken {
+    pub token   : Token,
+    pub text    : String,       // Source text of the token.
+    pub leading : Vec<Trivia>,  // Trivia between the previous token and this one.
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct SyntaxNode {
+    pub kind     : NodeKind,
+    pub children : Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    // Tokens of the node in source order, the ones of the children included.
+    pub fn tokens(&self) -> Vec<&SyntaxToken> {
+        let mut out = Vec::new();
+        self.collect_tokens(&mut out);
+        out
+    }
+
+    fn collect_tokens<'a>(&'a self, out : &mut Vec<&'a SyntaxToken>) {
+        for child in &self.children {
+            match *child {
+                SyntaxElement::Node(ref n) => n.collect_tokens(out),
+                SyntaxElement::Token(ref t) => out.push(t),
+            }
+        }
+    }
+
+    // One line for each node and token, indented by depth.
+    pub fn dump(&self) -> String {
+        let mut out = String::new();
+        self.dump_rec(0, &mut out);
+        out
+    }
+
+    fn dump_rec(&self, depth : usize, out : &mut String) {
+        out.push_str(&format!("{}{:?}\n", "  ".repeat(depth), self.kind));
+        for child in &self.children {
+            match *child {
+                SyntaxElement::Node(ref n) => n.dump_rec(depth + 1, out),
+                SyntaxElement::Token(ref t) => {
+                    let pad = "  ".repeat(depth + 1);
+                    out.push_str(&format!("{}{:?} {:?}\n", pad, t.token.tag, t.text));
+                },
+            }
+        }
+    }
+}
+
+// The original source text.
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        for t in self.tokens() {
+            for trivia in &t.leading {
+                write!(f, "{}", trivia.text)?;
+            }
+            write!(f, "{}", t.text)?;
+        }
+        Ok(())
+    }
+}
+
+// Parse the whole input keeping every token. The tree is complete even when
+// there are syntax errors, which are returned along with it.
+pub fn parse(scanner : Scanner) -> (SyntaxNode, Vec<ParseError>) {
+    let mut parser = Parser::with_cst(scanner);
+    let errors = match parser.parse() {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors,
+    };
+    (parser.take_cst().unwrap(), errors)
+}
+
+// Position in the tree where a node can be opened later, to wrap the
+// children added since. Used for left recursive rules.
+#[derive(Clone, Copy)]
+pub struct Checkpoint {
+    depth : usize,
+    index : usize,
+}
+
+// Builds the tree from the events of the parser.
+pub struct TreeBuilder {
+    stack        : Vec<(NodeKind, Vec<SyntaxElement>)>,   // Nodes still open.
+    root         : Option<SyntaxNode>,
+    offset       : usize,       // End of the last token added.
+    next_comment : usize,       // First comment of the scanner not yet attached.
+    skipped      : Vec<Token>,  // Error tokens not yet attached.
+}
+
+impl TreeBuilder {
+    pub fn new() -> Self {
+        TreeBuilder {
+            stack : Vec::new(),
+            root : None,
+            offset : 0,
+            next_comment : 0,
+            skipped : Vec::new(),
+        }
+    }
+
+    pub fn start_node(&mut self, kind : NodeKind) {
+        self.stack.push((kind, Vec::new()));
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            depth : self.stack.len(),
+            index : self.stack.last().map_or(0, |n| n.1.len()),
+        }
+    }
+
+    // Open a node holding everything added after the checkpoint.
+    pub fn start_node_at(&mut self, cp : Checkpoint, kind : NodeKind) {
+        assert!(cp.depth == self.stack.len(), "Checkpoint used at a different depth.");
+        let children = self.stack.last_mut().unwrap().1.split_off(cp.index);
+        self.stack.push((kind, children));
+    }
+
+    pub fn finish_node(&mut self) {
+        let (kind, children) = self.stack.pop().expect("No node to finish.");
+        let node = SyntaxNode { kind, children };
+        match self.stack.last_mut() {
+            Some(parent) => parent.1.push(SyntaxElement::Node(node)),
+            None => self.root = Some(node),
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    // Close the nodes left open by a syntax error.
+    pub fn unwind(&mut self, depth : usize) {
+        while self.stack.len() > depth {
+            self.finish_node();
+        }
+    }
+
+    // Token the parser does not see, kept as trivia of the next one.
+    pub fn skip(&mut self, tok : Token) {
+        self.skipped.push(tok);
+    }
+
+    pub fn token(&mut self, tok : Token, scanner : &Scanner) {
+        let src = scanner.source();
+        let leading = self.trivia(tok.span.lo, scanner);
+        // Anything after the end of file is part of it.
+        let hi = if tok.tag == Tag::Eof { src.len() } else { tok.span.hi };
+        let t = SyntaxToken {
+            text    : String::from(&src[tok.span.lo..hi]),
+            token   : tok,
+            leading,
+        };
+        self.offset = hi;
+        self.stack.last_mut().expect("Token outside of a node.").1.push(SyntaxElement::Token(t));
+    }
+
+    // Split the text between the last token and `hi` in whitespace, comments
+    // and skipped characters.
+    fn trivia(&mut self, hi : usize, scanner : &Scanner) -> Vec<Trivia> {
+        let src = scanner.source();
+        let mut out = Vec::new();
+        let mut pos = self.offset;
+        let mut skipped = mem::take(&mut self.skipped).into_iter().peekable();
+        while pos < hi {
+            while skipped.peek().is_some_and(|t| t.span.lo < pos) {
+                skipped.next();
+            }
+            let comment = scanner.comments.get(self.next_comment)
+                .filter(|c| c.span.lo == pos).map(|c| c.span.hi);
+            let bad = skipped.peek().filter(|t| t.span.lo == pos).map(|t| t.span.hi);
+            let (kind, end) = if let Some(end) = comment {
+                self.next_comment += 1;
+                (TriviaKind::Comment, end)
+            } else if let Some(end) = bad {
+                skipped.next();
+                (TriviaKind::Skipped, end)
+            } else {
+                let len = src[pos..hi].find(|c| c != ' ' && c != '\t' && c != '\n' && c != '\r')
+                    .unwrap_or(hi - pos);
+                if len > 0 {
+                    (TriviaKind::Whitespace, pos + len)
+                } else {
+                    // Not expected, kept anyway so that nothing is lost.
+                    let c = src[pos..].chars().next().unwrap();
+                    (TriviaKind::Skipped, pos + c.len_utf8())
+                }
+            };
+            out.push(Trivia { kind, text : String::from(&src[pos..end]) });
+            pos = end;
+        }
+        self.skipped = skipped.collect();
+        out
+    }
+
+    pub fn finish(mut self) -> SyntaxNode {
+        self.unwind(0);
+        self.root.expect("Empty syntax tree.")
+    }
+}
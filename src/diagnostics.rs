@@ -0,0 +1,258 @@
+/*
+    Compiler diagnostics: errors and warnings with their location in the
+    source, rendered with the offending lines and carets underneath.
+*/
+use lexer::Span;
+use std::fmt;
+
+/* Error codes. */
+pub const E_SYNTAX             : &str = "E0001";
+pub const E_LEXICAL            : &str = "E0002";
+pub const E_UNDECLARED         : &str = "E0101";
+pub const E_DUPLICATE_DECL     : &str = "E0102";
+pub const E_ARRAY_DIM          : &str = "E0103";
+pub const E_BREAK_OUTSIDE_LOOP : &str = "E0104";
+pub const E_UNDECLARED_LABEL   : &str = "E0105";
+pub const E_DUPLICATE_CASE     : &str = "E0106";
+pub const E_RETURN_OUTSIDE_FN  : &str = "E0107";
+pub const E_ENTRY_POINT        : &str = "E0108";
+pub const E_ASSIGN_CONST       : &str = "E0109";
+pub const E_MISMATCHED_TYPES   : &str = "E0201";
+pub const E_NOT_NUMERIC        : &str = "E0202";
+pub const E_ARG_COUNT          : &str = "E0203";
+pub const E_NO_VALUE           : &str = "E0204";
+pub const E_MISSING_RETURN     : &str = "E0205";
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Severity::Error   => "error",
+            Severity::Warning => "warning",
+            Severity::Note    => "note",
+            Severity::Help    => "help",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+// A span with an explanation, printed under the source line.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Label {
+    pub span    : Span,
+    pub message : String,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct Diagnostic {
+    pub severity : Severity,
+    pub code     : Option<&'static str>,
+    pub message  : String,
+    pub primary  : Label,
+    pub labels   : Vec<Label>,  // Secondary locations.
+    pub notes    : Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity : Severity, message : &str, span : Span) -> Self {
+        Diagnostic {
+            severity,
+            code     : None,
+            message  : String::from(message),
+            primary  : Label { span, message : String::new() },
+            labels   : Vec::new(),
+            notes    : Vec::new(),
+        }
+    }
+
+    pub fn error(message : &str, span : Span) -> Self {
+        Diagnostic::new(Severity::Error, message, span)
+    }
+
+    pub fn warning(message : &str, span : Span) -> Self {
+        Diagnostic::new(Severity::Warning, message, span)
+    }
+
+    pub fn with_code(mut self, code : &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    // Message printed under the primary span.
+    pub fn with_primary_label(mut self, message : &str) -> Self {
+        self.primary.message = String::from(message);
+        self
+    }
+
+    pub fn with_label(mut self, span : Span, message : &str) -> Self {
+        self.labels.push(Label { span, message : String::from(message) });
+        self
+    }
+
+    pub fn with_note(mut self, note : &str) -> Self {
+        self.notes.push(String::from(note));
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    // Render the diagnostic with the source lines it refers to.
+    pub fn render(&self, file : &SourceFile) -> String {
+        let mut out = String::new();
+        out.push_str(&self.header());
+        out.push('\n');
+
+        let sp = self.primary.span;
+        let (line, col) = file.position(sp.lo);
+        let mut lines : Vec<usize> = vec![line];
+        for l in &self.labels {
+            lines.push(file.position(l.span.lo).0);
+        }
+        lines.sort();
+        lines.dedup();
+        let width = format!("{}", lines[lines.len() - 1]).len();
+        let pad = " ".repeat(width);
+
+        out.push_str(&format!("{}--> {}:{}:{}\n", pad, file.name, line, col));
+        out.push_str(&format!("{} |\n", pad));
+        let mut prev = None;
+        for &n in &lines {
+            if let Some(p) = prev {
+                if n > p + 1 {
+                    out.push_str(&format!("{} |\n", pad));
+                }
+            }
+            prev = Some(n);
+            let text = file.line(n);
+            out.push_str(&format!("{:>w$} | {}\n", n, text, w = width));
+            // Primary label first, then the secondary ones in order.
+            let all = Some(('^', &self.primary)).into_iter()
+                .chain(self.labels.iter().map(|l| ('-', l)));
+            for (mark, label) in all {
+                if file.position(label.span.lo).0 != n {
+                    continue;
+                }
+                let underline = file.underline(label.span, mark);
+                if label.message.is_empty() {
+                    out.push_str(&format!("{} | {}\n", pad, underline));
+                } else {
+                    out.push_str(&format!("{} | {} {}\n", pad, underline, label.message));
+                }
+            }
+        }
+        for note in &self.notes {
+            out.push_str(&format!("{} = note: {}\n", pad, note));
+        }
+        out
+    }
+
+    fn header(&self) -> String {
+        match self.code {
+            Some(code) => format!("{}[{}]: {}", self.severity, code, self.message),
+            None => format!("{}: {}", self.severity, self.message),
+        }
+    }
+}
+
+// Short form without source lines.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let sp = self.primary.span;
+        write!(f, "{}:{}: {}", sp.line, sp.col, self.header())
+    }
+}
+
+/* Diagnostics reported by several passes. */
+
+pub fn undeclared(name : &str, span : Span) -> Diagnostic {
+    let msg = format!("undeclared identifier `{}`", name);
+    Diagnostic::error(&msg, span)
+        .with_code(E_UNDECLARED)
+        .with_primary_label("not found in this scope")
+}
+
+pub fn redeclared(name : &str, span : Span, prev : Span) -> Diagnostic {
+    let msg = format!("`{}` is already declared in this block", name);
+    Diagnostic::error(&msg, span)
+        .with_code(E_DUPLICATE_DECL)
+        .with_primary_label("redeclared here")
+        .with_label(prev, "previous declaration here")
+}
+
+// Source text with the offsets of the beginning of each line.
+pub struct SourceFile {
+    pub name    : String,
+    pub text    : String,
+    line_starts : Vec<usize>,
+}
+
+impl SourceFile {
+    pub fn new(name : &str, text : &str) -> Self {
+        let mut line_starts = vec![0];
+        for (i, c) in text.char_indices() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        SourceFile {
+            name : String::from(name),
+            text : String::from(text),
+            line_starts,
+        }
+    }
+
+    // Line and column, both starting from 1, of a byte offset.
+    pub fn position(&self, offset : usize) -> (usize, usize) {
+        let offset = if offset > self.text.len() { self.text.len() } else { offset };
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        let col = self.text[self.line_starts[line]..offset].chars().count();
+        (line + 1, col + 1)
+    }
+
+    // Text of the given line, without the line terminator.
+    pub fn line(&self, n : usize) -> &str {
+        let start = self.line_starts[n - 1];
+        let end = if n < self.line_starts.len() {
+            self.line_starts[n] - 1
+        } else {
+            self.text.len()
+        };
+        self.text[start..end].trim_end_matches('\r')
+    }
+
+    // Marks under the part of the span lying on its first line. Tabs are kept
+    // so that the marks stay aligned with the text.
+    fn underline(&self, span : Span, mark : char) -> String {
+        let (n, col) = self.position(span.lo);
+        let text = self.line(n);
+        let mut out = String::new();
+        let mut chars = text.chars();
+        for _ in 1..col {
+            match chars.next() {
+                Some('\t') => out.push('\t'),
+                _ => out.push(' '),
+            }
+        }
+        let len = if span.hi > span.lo {
+            let end = self.line_starts[n - 1] + text.len();
+            let hi = if span.hi < end { span.hi } else { end };
+            if hi > span.lo { self.text[span.lo..hi].chars().count() } else { 1 }
+        } else { 1 };
+        for _ in 0..len {
+            out.push(mark);
+        }
+        out
+    }
+}
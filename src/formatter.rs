@@ -0,0 +1,479 @@
+/*
+    Source formatter: prints a program back with a canonical layout. Blocks
+    are indented by four spaces, there is one declaration or statement per
+    line and binary operators are surrounded by single spaces. Parentheses
+    written in the source are part of the tree and are kept, others are only
+    added where precedence requires them.
+*/
+use ast::statement::{Program, Item, Function, Block, Decl, Type, Statement, StatementKind, BasicType, Case, CaseLabel};
+use ast::expression::{BoolExpr, BoolExprKind, NumExpr, NumExprKind, Loc, LocKind, Relop};
+use lexer::{Scanner, Comment, Span};
+use parser::Parser;
+use diagnostics::Diagnostic;
+
+const INDENT : &str = "    ";
+
+// Format a whole source file, comments included. Sources with lexical or
+// syntax errors are not formatted.
+pub fn format_source(scanner : Scanner) -> Result<String, Vec<Diagnostic>> {
+    let mut parser = Parser::new(scanner);
+    let program = parser.parse_program()?;
+    Ok(format_program(&program, parser.scanner.source(), parser.scanner.comments.clone()))
+}
+
+// Format a tree with the source it was parsed from and its comments. A tree
+// built by hand has an empty source and no comments.
+pub fn format_program(program : &Program, source : &str, comments : Vec<Comment>) -> String {
+    let mut f = Formatter::new(source, comments);
+    f.program(program);
+    f.out
+}
+
+struct Formatter<'a> {
+    out          : String,
+    indent       : usize,
+    source       : &'a str,     // Empty when formatting a tree built by hand.
+    comments     : Vec<Comment>,
+    next_comment : usize,       // First comment not printed yet.
+    last_hi      : usize,       // End of the last item printed, in the source.
+}
+
+impl<'a> Formatter<'a> {
+    fn new(source : &'a str, comments : Vec<Comment>) -> Self {
+        Formatter {
+            out : String::new(),
+            indent : 0,
+            source,
+            comments,
+            next_comment : 0,
+            last_hi : 0,
+        }
+    }
+
+    // Functions and the block are set apart from the other items by an
+    // empty line.
+    fn program(&mut self, p : &Program) {
+        let mut prev_function = false;
+        for (i, item) in p.items.iter().enumerate() {
+            let function = matches!(*item, Item::Function(_));
+            if i > 0 && (function || prev_function) {
+                self.out.push('\n');
+            }
+            prev_function = function;
+            let span = match *item {
+                Item::Global(ref d) => d.span,
+                Item::Const(ref c) => c.span,
+                Item::Function(ref f) => f.span.to(f.body.span),
+            };
+            self.start_item(span);
+            match *item {
+                Item::Global(ref d) => self.decl(d),
+                Item::Const(ref c) => {
+                    let s = format!("const {} {} = {};", basic_to_str(c.basic_type), c.name, bool_to_string(&c.value, 0));
+                    self.out.push_str(&s);
+                },
+                Item::Function(ref f) => self.function(f),
+            }
+            self.end_item(span);
+        }
+        if let Some(ref b) = p.block {
+            if !p.items.is_empty() {
+                self.out.push('\n');
+            }
+            self.comments_before(b.span.lo);
+            self.block(b);
+            self.trailing_comment(b.span);
+            self.out.push('\n');
+        }
+        let end = self.source.len();
+        self.comments_before(end + 1);
+    }
+
+    fn function(&mut self, f : &Function) {
+        let ret = f.ret.map_or("void", basic_to_str);
+        let params : Vec<String> = f.params.iter()
+            .map(|d| format!("{} {}", type_to_string(&d.type_id), d.id))
+            .collect();
+        self.out.push_str(&format!("{} {}({}) ", ret, f.name, params.join(", ")));
+        self.block(&f.body);
+    }
+
+    // The cursor is where the opening brace goes.
+    fn block(&mut self, b : &Block) {
+        let empty = b.decls.is_empty() && b.stmts.is_empty() &&
+            self.comments.get(self.next_comment).is_none_or(|c| c.span.lo >= b.span.hi);
+        if empty {
+            self.out.push_str("{}");
+            self.last_hi = b.span.hi;
+            return;
+        }
+        self.out.push_str("{\n");
+        self.indent += 1;
+        // Declarations and statements are printed in source order.
+        let (mut i, mut j) = (0, 0);
+        while i < b.decls.len() || j < b.stmts.len() {
+            if j == b.stmts.len() || (i < b.decls.len() && b.decls[i].span.lo <= b.stmts[j].span.lo) {
+                let d = &b.decls[i];
+                self.start_item(d.span);
+                self.decl(d);
+                self.end_item(d.span);
+                i += 1;
+            } else {
+                let s = &b.stmts[j];
+                self.start_item(s.span);
+                self.statement(s);
+                self.end_item(s.span);
+                j += 1;
+            }
+        }
+        self.comments_before(b.span.hi);
+        self.indent -= 1;
+        self.write_indent();
+        self.out.push('}');
+        self.last_hi = b.span.hi;
+    }
+
+    fn start_item(&mut self, span : Span) {
+        self.comments_before(span.lo);
+        self.blank_line(span.lo);
+        self.write_indent();
+    }
+
+    fn end_item(&mut self, span : Span) {
+        self.trailing_comment(span);
+        self.last_hi = span.hi;
+        self.out.push('\n');
+    }
+
+    fn decl(&mut self, d : &Decl) {
+        let s = format!("{} {};", type_to_string(&d.type_id), d.id);
+        self.out.push_str(&s);
+    }
+
+    // Statement starting at the cursor, without the final newline.
+    fn statement(&mut self, s : &Statement) {
+        match s.kind {
+            StatementKind::Assign(..) => {
+                let s = format!("{};", assign_to_string(s));
+                self.out.push_str(&s);
+            },
+            StatementKind::If(ref cond, ref s1) => {
+                self.out.push_str(&format!("if ({})", bool_to_string(cond, 0)));
+                self.body(s1);
+            },
+            StatementKind::IfElse(ref cond, ref s1, ref s2) => {
+                self.out.push_str(&format!("if ({})", bool_to_string(cond, 0)));
+                let braces = if ends_with_if(s1) {
+                    // Without braces the else would go to the inner if.
+                    self.braced(s1);
+                    true
+                } else {
+                    self.body(s1)
+                };
+                if braces {
+                    self.out.push_str(" else");
+                } else {
+                    self.out.push('\n');
+                    self.write_indent();
+                    self.out.push_str("else");
+                }
+                match s2.kind {
+                    StatementKind::If(..) | StatementKind::IfElse(..) => {
+                        // else if chain.
+                        self.out.push(' ');
+                        self.statement(s2);
+                    },
+                    _ => { self.body(s2); },
+                }
+            },
+            StatementKind::While(ref cond, ref s1) => {
+                self.out.push_str(&format!("while ({})", bool_to_string(cond, 0)));
+                self.body(s1);
+            },
+            StatementKind::For(ref f) => {
+                let init = match (f.decl.as_ref(), f.init.as_ref()) {
+                    (Some(d), Some(init)) => {
+                        format!("{} {}", type_to_string(&d.type_id), assign_to_string(init))
+                    },
+                    (_, Some(init)) => assign_to_string(init),
+                    _ => String::new(),
+                };
+                let cond = f.cond.as_ref().map_or(String::new(), |e| format!(" {}", bool_to_string(e, 0)));
+                let step = f.step.as_ref().map_or(String::new(), |s| format!(" {}", assign_to_string(s)));
+                self.out.push_str(&format!("for ({};{};{})", init, cond, step));
+                self.body(&f.body);
+            },
+            StatementKind::DoWhile(ref s1, ref cond) => {
+                self.out.push_str("do");
+                if self.body(s1) {
+                    self.out.push(' ');
+                } else {
+                    self.out.push('\n');
+                    self.write_indent();
+                }
+                self.out.push_str(&format!("while ({});", bool_to_string(cond, 0)));
+            },
+            StatementKind::Switch(ref e, ref cases) => {
+                self.out.push_str(&format!("switch ({}) ", bool_to_string(e, 0)));
+                self.cases(cases, s.span);
+            },
+            StatementKind::Labeled(ref name, ref s1) => {
+                self.out.push_str(&format!("{}: ", name));
+                self.statement(s1);
+            },
+            StatementKind::Break(ref label) => self.out.push_str(&jump("break", label)),
+            StatementKind::Continue(ref label) => self.out.push_str(&jump("continue", label)),
+            StatementKind::Return(None) => self.out.push_str("return;"),
+            StatementKind::Return(Some(ref e)) => {
+                self.out.push_str(&format!("return {};", bool_to_string(e, 0)));
+            },
+            StatementKind::Call(ref name, ref args) => {
+                self.out.push_str(&format!("{};", call_to_string(name, args)));
+            },
+            StatementKind::BlockStmt(ref b) => self.block(b),
+            StatementKind::Error => unreachable!("Formatting with syntax errors."),
+        }
+    }
+
+    // Like a block, with the statements of each case indented under it.
+    fn cases(&mut self, cases : &[Case], span : Span) {
+        if cases.is_empty() {
+            self.out.push_str("{}");
+            self.last_hi = span.hi;
+            return;
+        }
+        self.out.push_str("{\n");
+        self.indent += 1;
+        for c in cases {
+            self.start_item(c.span);
+            self.out.push_str(&match c.label {
+                CaseLabel::Int(x) => format!("case {}:", x),
+                CaseLabel::Bool(true) => String::from("case True:"),
+                CaseLabel::Bool(false) => String::from("case False:"),
+                CaseLabel::Default => String::from("default:"),
+            });
+            self.end_item(c.span);
+            self.indent += 1;
+            for s in &c.stmts {
+                self.start_item(s.span);
+                self.statement(s);
+                self.end_item(s.span);
+            }
+            self.indent -= 1;
+        }
+        self.comments_before(span.hi);
+        self.indent -= 1;
+        self.write_indent();
+        self.out.push('}');
+        self.last_hi = span.hi;
+    }
+
+    // Body of if and while: a block on the same line, anything else on its
+    // own indented line. Returns true for blocks.
+    fn body(&mut self, s : &Statement) -> bool {
+        if let StatementKind::BlockStmt(ref b) = s.kind {
+            self.out.push(' ');
+            self.block(b);
+            true
+        } else {
+            self.out.push('\n');
+            self.indent += 1;
+            self.comments_before(s.span.lo);
+            self.write_indent();
+            self.statement(s);
+            self.indent -= 1;
+            false
+        }
+    }
+
+    // Statement wrapped in a block that is not in the tree.
+    fn braced(&mut self, s : &Statement) {
+        self.out.push_str(" {\n");
+        self.indent += 1;
+        self.write_indent();
+        self.statement(s);
+        self.out.push('\n');
+        self.indent -= 1;
+        self.write_indent();
+        self.out.push('}');
+    }
+
+    /* Comments */
+
+    // Print on their own lines the comments starting before the offset.
+    fn comments_before(&mut self, lo : usize) {
+        while self.next_comment < self.comments.len() && self.comments[self.next_comment].span.lo < lo {
+            let c = self.comments[self.next_comment].clone();
+            self.next_comment += 1;
+            self.blank_line(c.span.lo);
+            self.write_indent();
+            self.out.push_str(&c.text);
+            self.out.push('\n');
+            self.last_hi = c.span.hi;
+        }
+    }
+
+    // Comment on the same line of the end of an item.
+    fn trailing_comment(&mut self, span : Span) {
+        if let Some(c) = self.comments.get(self.next_comment).cloned() {
+            let same_line = c.span.lo >= span.hi &&
+                self.source[span.hi..c.span.lo].chars().all(|c| c == ' ' || c == '\t');
+            if same_line {
+                self.next_comment += 1;
+                self.out.push(' ');
+                self.out.push_str(&c.text);
+            }
+        }
+    }
+
+    // Empty lines between items are kept, at most one.
+    fn blank_line(&mut self, lo : usize) {
+        if self.source.is_empty() || self.out.is_empty() || self.out.ends_with("{\n") ||
+            self.out.ends_with("\n\n") || lo <= self.last_hi {
+            return;
+        }
+        if self.source[self.last_hi..lo].matches('\n').count() > 1 {
+            self.out.push('\n');
+        }
+    }
+
+    fn write_indent(&mut self) {
+        for _ in 0..self.indent {
+            self.out.push_str(INDENT);
+        }
+    }
+}
+
+// True if the statement ends with an if without else, that would take an
+// else written after it.
+fn ends_with_if(s : &Statement) -> bool {
+    match s.kind {
+        StatementKind::If(..) => true,
+        StatementKind::IfElse(_, _, ref s2) => ends_with_if(s2),
+        StatementKind::While(_, ref s1) | StatementKind::Labeled(_, ref s1) => ends_with_if(s1),
+        StatementKind::For(ref f) => ends_with_if(&f.body),
+        _ => false,
+    }
+}
+
+// Assignment without the semicolon, as in the header of a for.
+fn assign_to_string(s : &Statement) -> String {
+    match s.kind {
+        StatementKind::Assign(ref l, ref e) => format!("{} = {}", loc_to_string(l), bool_to_string(e, 0)),
+        _ => unreachable!("Not an assignment."),
+    }
+}
+
+fn jump(keyword : &str, label : &Option<String>) -> String {
+    match *label {
+        Some(ref l) => format!("{} {};", keyword, l),
+        None => format!("{};", keyword),
+    }
+}
+
+fn call_to_string(name : &str, args : &[BoolExpr]) -> String {
+    let args : Vec<String> = args.iter().map(|e| bool_to_string(e, 0)).collect();
+    format!("{}({})", name, args.join(", "))
+}
+
+fn basic_to_str(t : BasicType) -> &'static str {
+    match t {
+        BasicType::Int => "int",
+        BasicType::Float => "float",
+        BasicType::Bool => "bool",
+    }
+}
+
+fn type_to_string(t : &Type) -> String {
+    let mut s = String::from(basic_to_str(t.basic_type));
+    for n in t.dims() {
+        s.push_str(&format!("[{}]", n));
+    }
+    s
+}
+
+/* Expressions. Binary operators are left associative: the right operand
+   needs a precedence higher than the operator. */
+
+fn bool_prec(e : &BoolExpr) -> u32 {
+    match e.kind {
+        BoolExprKind::Or(..) => 1,
+        BoolExprKind::And(..) => 2,
+        BoolExprKind::Eq(..) | BoolExprKind::Neq(..) => 3,
+        BoolExprKind::Relop(..) => 4,
+        BoolExprKind::NumExpr(ref n) => num_prec(n),
+    }
+}
+
+fn num_prec(e : &NumExpr) -> u32 {
+    match e.kind {
+        NumExprKind::Add(..) | NumExprKind::Sub(..) => 5,
+        NumExprKind::Mul(..) | NumExprKind::Div(..) => 6,
+        NumExprKind::Not(..) | NumExprKind::Minus(..) => 7,
+        _ => 8,
+    }
+}
+
+// Expression with parentheses around it if its precedence is below min.
+fn bool_to_string(e : &BoolExpr, min : u32) -> String {
+    let prec = bool_prec(e);
+    let s = match e.kind {
+        BoolExprKind::Or(ref a, ref b) => bool_binary(a, "||", b, prec),
+        BoolExprKind::And(ref a, ref b) => bool_binary(a, "&&", b, prec),
+        BoolExprKind::Eq(ref a, ref b) => bool_binary(a, "==", b, prec),
+        BoolExprKind::Neq(ref a, ref b) => bool_binary(a, "!=", b, prec),
+        BoolExprKind::Relop(ref op, ref a, ref b) => {
+            let op = match *op {
+                Relop::Ge => ">=",
+                Relop::Gr => ">",
+                Relop::Leq => "<=",
+                Relop::Les => "<",
+            };
+            // Relational operators are not associative.
+            format!("{} {} {}", num_to_string(a, prec + 1), op, num_to_string(b, prec + 1))
+        },
+        BoolExprKind::NumExpr(ref n) => num_to_string(n, 0),
+    };
+    if prec < min { format!("({})", s) } else { s }
+}
+
+fn bool_binary(a : &BoolExpr, op : &str, b : &BoolExpr, prec : u32) -> String {
+    format!("{} {} {}", bool_to_string(a, prec), op, bool_to_string(b, prec + 1))
+}
+
+fn num_to_string(e : &NumExpr, min : u32) -> String {
+    let prec = num_prec(e);
+    let s = match e.kind {
+        NumExprKind::Add(ref a, ref b) => num_binary(a, "+", b, prec),
+        NumExprKind::Sub(ref a, ref b) => num_binary(a, "-", b, prec),
+        NumExprKind::Mul(ref a, ref b) => num_binary(a, "*", b, prec),
+        NumExprKind::Div(ref a, ref b) => num_binary(a, "/", b, prec),
+        NumExprKind::Not(ref a) => format!("!{}", num_to_string(a, prec)),
+        NumExprKind::Minus(ref a) => format!("-{}", num_to_string(a, prec)),
+        NumExprKind::Expr(ref b) => format!("({})", bool_to_string(b, 0)),
+        NumExprKind::Loc(ref l) => loc_to_string(l),
+        NumExprKind::Call(ref name, ref args) => call_to_string(name, args),
+        NumExprKind::Num(x) => format!("{}", x),
+        NumExprKind::Real(x) => format!("{:?}", x),
+        NumExprKind::True => String::from("True"),
+        NumExprKind::False => String::from("False"),
+    };
+    if prec < min { format!("({})", s) } else { s }
+}
+
+fn num_binary(a : &NumExpr, op : &str, b : &NumExpr, prec : u32) -> String {
+    format!("{} {} {}", num_to_string(a, prec), op, num_to_string(b, prec + 1))
+}
+
+fn loc_to_string(l : &Loc) -> String {
+    match l.kind {
+        LocKind::Ide(ref s) => s.clone(),
+        LocKind::Index(ref s, ref v) => {
+            let mut out = s.clone();
+            for e in v {
+                out.push_str(&format!("[{}]", bool_to_string(e, 0)));
+            }
+            out
+        },
+    }
+}
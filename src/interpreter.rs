@@ -1,65 +1,234 @@
-use code_generator::{OpCode, AddressMode, AddressCode, Address, IntermediateRepresentation};
+use code_generator::{OpCode, AddressMode, AddressCode, Address, IntermediateRepresentation, Variable};
+use ast::statement::BasicType;
+use std::fmt;
+use std::mem;
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Value {
+    Int(i32),
+    Float(f64),
+    Bool(bool),
+}
+
+impl Value {
+    pub fn as_int(&self) -> i32 {
+        match *self {
+            Value::Int(x) => x,
+            Value::Float(x) => x as i32,
+            Value::Bool(b) => b as i32,
+        }
+    }
+
+    pub fn as_float(&self) -> f64 {
+        match *self {
+            Value::Int(x) => x as f64,
+            Value::Float(x) => x,
+            Value::Bool(b) => b as i32 as f64,
+        }
+    }
+
+    pub fn is_true(&self) -> bool {
+        match *self {
+            Value::Int(x) => x != 0,
+            Value::Float(x) => x != 0.0,
+            Value::Bool(b) => b,
+        }
+    }
+
+    fn sign(&self) -> i32 {
+        match *self {
+            Value::Int(x) => x.signum(),
+            Value::Float(x) => if x > 0.0 { 1 } else if x < 0.0 { -1 } else { 0 },
+            Value::Bool(b) => b as i32,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Int(x) => write!(f, "{}", x),
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+// Error that stops the program while it runs.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum RuntimeError {
+    DivisionByZero,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RuntimeError::DivisionByZero => write!(f, "division by zero"),
+        }
+    }
+}
+
+// Registers of a caller, with where to go on and where to put the value
+// once the callee returns.
+struct Activation {
+    regs   : Vec<Value>,
+    ret    : usize,
+    result : Address,
+}
 
 pub struct Interpreter {
     code      : Vec<AddressCode>,
+    float_constants : Vec<f64>,
+    variables : Vec<Variable>,
     stack     : Vec<i32>,
-    regs      : Vec<i32>,
+    regs      : Vec<Value>,
+    statics   : Vec<Value>,
+    params    : Vec<Value>,
+    calls     : Vec<Activation>,
 }
 
 impl Interpreter {
     pub fn new(code : IntermediateRepresentation) -> Self{
         Interpreter {
             code  : code.instructions,
+            float_constants : code.float_constants,
+            variables : code.variables,
             stack : Vec::new(),
-            regs  : Vec::new(),    
+            regs  : Vec::new(),
+            statics : Vec::new(),
+            params : Vec::new(),
+            calls : Vec::new(),
         }
     }
-    
-    pub fn execute(&mut self) {
+
+    // Integer arithmetic wraps around on overflow, as in two's complement.
+    pub fn execute(&mut self) -> Result<(), RuntimeError> {
         let mut i = 0;
         while i < self.code.len() {
-            let instr = &self.code[i];
-            let res = instr.res.place as usize;
-            let x = self.regs[instr.x.place as usize];
-            let y = self.regs[instr.y.place as usize];
+            let (op, res, x, y) = {
+                let instr = &self.code[i];
+                (instr.op, instr.res, self.read(instr.x), self.read(instr.y))
+            };
             // Update instruction counter.
             i = i + 1;
-            match instr.op {
-                OpCode::Mov     => self.regs[res] = x,
+            match op {
+                OpCode::Mov     => self.write(res, x),
                 // Boolean operators.
-                OpCode::And     => self.regs[res] = Interpreter::and(x, y),
-                OpCode::Or      => self.regs[res] = Interpreter::or(x, y),
-                OpCode::Not     => self.regs[res] = Interpreter::not(x),
-                OpCode::IsPos   => self.regs[res] = {if x > 0 {1} else {0}},
-                OpCode::IsNeg   => self.regs[res] = {if x < 0 {1} else {0}},
+                OpCode::And     => self.write(res, Interpreter::and(x, y)),
+                OpCode::Or      => self.write(res, Interpreter::or(x, y)),
+                OpCode::Not     => self.write(res, Interpreter::not(x)),
+                OpCode::IsPos   => self.write(res, Value::Bool(x.sign() > 0)),
+                OpCode::IsNeg   => self.write(res, Value::Bool(x.sign() < 0)),
                 // Num operators.
-                OpCode::Add     => self.regs[res] = x + y,
-                OpCode::Sub     => self.regs[res] = x - y,
-                OpCode::Mul     => self.regs[res] = x * y,
-                OpCode::Div     => self.regs[res] = x / y,
-                OpCode::Minus   => self.regs[res] = -x,
-                // Jump.
-                OpCode::Goto    => i = instr.res.place as usize,
-                OpCode::JmpZ    => if x == 0 { i = instr.res.place as usize },
+                OpCode::Add     => self.write(res, Value::Int(x.as_int().wrapping_add(y.as_int()))),
+                OpCode::Sub     => self.write(res, Value::Int(x.as_int().wrapping_sub(y.as_int()))),
+                OpCode::Mul     => self.write(res, Value::Int(x.as_int().wrapping_mul(y.as_int()))),
+                OpCode::Div     => {
+                    if y.as_int() == 0 {
+                        return Err(RuntimeError::DivisionByZero);
+                    }
+                    self.write(res, Value::Int(x.as_int().wrapping_div(y.as_int())))
+                },
+                OpCode::Minus   => self.write(res, Value::Int(x.as_int().wrapping_neg())),
+                // Float operators.
+                OpCode::FAdd    => self.write(res, Value::Float(x.as_float() + y.as_float())),
+                OpCode::FSub    => self.write(res, Value::Float(x.as_float() - y.as_float())),
+                OpCode::FMul    => self.write(res, Value::Float(x.as_float() * y.as_float())),
+                OpCode::FDiv    => self.write(res, Value::Float(x.as_float() / y.as_float())),
+                OpCode::FMinus  => self.write(res, Value::Float(-x.as_float())),
+                OpCode::IntToFloat => self.write(res, Value::Float(x.as_float())),
+                OpCode::FloatToInt => self.write(res, Value::Int(x.as_int())),
+                // Jump. Labels are instruction ids, starting from 1.
+                OpCode::Goto    => i = (res.place - 1) as usize,
+                OpCode::JmpZ    => if !x.is_true() { i = (res.place - 1) as usize },
+                OpCode::JmpInd  => i = (res.place - 1 + x.as_int()) as usize,
+                // Calls. The callee gets new registers, with the arguments
+                // after the stack pointer.
+                OpCode::Param   => self.params.push(x),
+                OpCode::Call    => {
+                    let n = self.params.len() - y.as_int() as usize;
+                    let mut regs = vec![Value::Int(0); 2];
+                    regs[1] = self.regs.get(1).cloned().unwrap_or(Value::Int(0));
+                    regs.append(&mut self.params.split_off(n));
+                    self.calls.push(Activation {
+                        regs   : mem::replace(&mut self.regs, regs),
+                        ret    : i,
+                        result : res,
+                    });
+                    i = (x.as_int() - 1) as usize;
+                },
+                OpCode::Ret     => {
+                    let caller = self.calls.pop().expect("Return without a call.");
+                    self.regs = caller.regs;
+                    self.write(caller.result, x);
+                    i = caller.ret;
+                },
             }
         }
+        Ok(())
+    }
+
+    // Name and value of each variable, in the order of declaration.
+    pub fn variables(&self) -> Vec<(&str, Value)> {
+        self.variables.iter()
+            .map(|v| (v.name.as_str(), self.read_variable(v)))
+            .collect()
+    }
+
+    pub fn dump_variables(&self) {
+        for (name, value) in self.variables() {
+            println!("{} = {}", name, value);
+        }
+    }
+
+    // Boolean constants are the integers 1 and 0, the declared type tells
+    // how to show them.
+    fn read_variable(&self, v : &Variable) -> Value {
+        let x = self.read(v.address);
+        match v.basic_type {
+            BasicType::Bool => Value::Bool(x.is_true()),
+            _ => x,
+        }
+    }
+
+    fn read(&self, addr : Address) -> Value {
+        match addr.mode {
+            AddressMode::Register | AddressMode::FramePointer => {
+                match self.regs.get(addr.place as usize) {
+                    Some(v) => *v,
+                    None => Value::Int(0),
+                }
+            },
+            AddressMode::Constant | AddressMode::Label => Value::Int(addr.place),
+            AddressMode::FloatConstant => Value::Float(self.float_constants[addr.place as usize]),
+            AddressMode::Static => {
+                match self.statics.get(addr.place as usize) {
+                    Some(v) => *v,
+                    None => Value::Int(0),
+                }
+            },
+        }
+    }
+
+    // Registers and static storage grow as they are written.
+    fn write(&mut self, addr : Address, v : Value) {
+        let n = addr.place as usize;
+        let cells = if addr.mode == AddressMode::Static { &mut self.statics } else { &mut self.regs };
+        if n >= cells.len() {
+            cells.resize(n + 1, Value::Int(0));
+        }
+        cells[n] = v;
+    }
+
+    fn and(x : Value, y : Value) -> Value {
+        Value::Bool(x.is_true() && y.is_true())
+    }
+
+    fn or(x : Value, y : Value) -> Value {
+        Value::Bool(x.is_true() || y.is_true())
     }
-    
-    fn and(x : i32, y : i32) -> i32 {
-        if x==1 && y==1 {
-            1
-        } else { 0 }
-    }
-    
-    fn or(x : i32, y : i32) -> i32 {
-        if x==1 || y==1 {
-            1
-        } else { 0 }
-    }
-    
-    fn not(x : i32) -> i32 {
-        if x==1 {
-            0
-        } else { 1 }
-    }
-}
\ No newline at end of file
+
+    fn not(x : Value) -> Value {
+        Value::Bool(!x.is_true())
+    }
+}
@@ -1,19 +1,35 @@
 use std::borrow::Cow;
 use std::io;
+use std::io::Read;
+use std::fs::File;
+use std::path::Path;
+use std::fmt;
+use diagnostics::{Diagnostic, E_LEXICAL};
 
-#[derive(PartialEq, Eq, Hash, Debug)]
+#[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
 pub enum Tag {
     Eof, // Finished.
-    Error, // Parsing error.
+    Error, // Lexical error.
     /* Reserved words. */
     If,
     Else,
     While,
+    For,
+    Do,
+    Switch,
+    Case,
+    Default,
     Break,
+    Continue,
+    Return,
+    Void,
+    Const,
     /* Separators. */
     CloseBlock,
     OpenBlock,
     SemiColon,
+    Colon,
+    Comma,
     LArrParen, RArrParen, LParen, RParen,
     Assign,
     /* Operators. */
@@ -25,12 +41,12 @@ pub enum Tag {
     Type,
     Ide,
     Num,
+    Real,
     True,
     False,
-    //Real(String), /* Hash trait not implemented for float. */
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub enum TokenInfo {
     NoInfo,
 
@@ -46,41 +62,216 @@ pub enum TokenInfo {
     Add, Sub,
     Mul, Div,
 
-    Int, Float,
+    Int, Float, Bool,
 
     Num(u32),
+    Real(f64),
     Ide(String),
+    Error(LexError),
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Debug, Clone)]
+pub enum LexError {
+    UnknownChar(char),  // Character that cannot start a token.
+    LoneChar(char),     // Single `|` or `&`.
+    UnterminatedComment,
+    MalformedNumber(String),    // Exponent without digits.
+    IntTooLarge(String),        // Integer literal overflowing u32.
+    RealOutOfRange(String),     // Real literal overflowing f64.
+}
+
+impl LexError {
+    pub fn to_diagnostic(&self, span : Span) -> Diagnostic {
+        match *self {
+            LexError::UnknownChar(c) => {
+                Diagnostic::error(&format!("unknown character `{}`", c), span)
+                    .with_code(E_LEXICAL)
+                    .with_primary_label("not a valid token")
+            },
+            LexError::LoneChar(c) => {
+                Diagnostic::error(&format!("unknown operator `{}`", c), span)
+                    .with_code(E_LEXICAL)
+                    .with_primary_label(&format!("did you mean `{}{}`?", c, c))
+            },
+            LexError::UnterminatedComment => {
+                Diagnostic::error("unterminated block comment", span)
+                    .with_code(E_LEXICAL)
+                    .with_primary_label("comment starts here")
+                    .with_note("block comments can be nested, each `/*` needs its own `*/`")
+            },
+            LexError::MalformedNumber(ref s) => {
+                Diagnostic::error(&format!("malformed number `{}`", s), span)
+                    .with_code(E_LEXICAL)
+                    .with_primary_label("expected digits in the exponent")
+            },
+            LexError::IntTooLarge(ref s) => {
+                Diagnostic::error(&format!("integer literal `{}` is too large", s), span)
+                    .with_code(E_LEXICAL)
+                    .with_primary_label("does not fit in 32 bits")
+            },
+            LexError::RealOutOfRange(ref s) => {
+                Diagnostic::error(&format!("real literal `{}` is out of range", s), span)
+                    .with_code(E_LEXICAL)
+                    .with_primary_label("does not fit in 64 bits")
+            },
+        }
+    }
+}
+
+impl Tag {
+    // Human readable description used in error messages.
+    pub fn describe(&self) -> &'static str {
+        match *self {
+            Tag::Eof => "end of file",
+            Tag::Error => "error",
+            Tag::If => "`if`",
+            Tag::Else => "`else`",
+            Tag::While => "`while`",
+            Tag::For => "`for`",
+            Tag::Do => "`do`",
+            Tag::Switch => "`switch`",
+            Tag::Case => "`case`",
+            Tag::Default => "`default`",
+            Tag::Break => "`break`",
+            Tag::Continue => "`continue`",
+            Tag::Return => "`return`",
+            Tag::Void => "`void`",
+            Tag::Const => "`const`",
+            Tag::CloseBlock => "`}`",
+            Tag::OpenBlock => "`{`",
+            Tag::SemiColon => "`;`",
+            Tag::Colon => "`:`",
+            Tag::Comma => "`,`",
+            Tag::LArrParen => "`[`",
+            Tag::RArrParen => "`]`",
+            Tag::LParen => "`(`",
+            Tag::RParen => "`)`",
+            Tag::Assign => "`=`",
+            Tag::BoolOp => "boolean operator",
+            Tag::RelOp => "relational operator",
+            Tag::Unary => "`!`",
+            Tag::NumOp => "arithmetic operator",
+            Tag::Type => "type",
+            Tag::Ide => "identifier",
+            Tag::Num => "number",
+            Tag::Real => "real number",
+            Tag::True => "`True`",
+            Tag::False => "`False`",
+        }
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum CommentKind {
+    Line,   // From `//` to the end of the line.
+    Block,  // Between `/*` and `*/`.
+}
+
+// Comments are not tokens, they are kept aside for tools that need them.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Comment {
+    pub kind : CommentKind,
+    pub text : String,  // Including the delimiters.
+    pub span : Span,
+}
+
+// Region of the source text covered by a token or an AST node.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub lo   : usize,   // Byte offset of the first character.
+    pub hi   : usize,   // Byte offset past the last character.
+    pub line : u32,     // Line of the first character, starting from 1.
+    pub col  : u32,     // Column of the first character, starting from 1.
+}
+
+impl Span {
+    // Span going from the start of self to the end of other.
+    pub fn to(&self, other : Span) -> Span {
+        Span {
+            lo   : self.lo,
+            hi   : other.hi,
+            line : self.line,
+            col  : self.col,
+        }
+    }
+}
+
+// Position of a character inside the input.
+#[derive(Clone, Copy)]
+struct Position {
+    offset : usize,
+    line   : u32,
+    col    : u32,
+}
+
+#[derive(PartialEq, Debug, Clone)]
 pub struct Token {
-    pub tag : Tag,
+    pub tag  : Tag,
     pub info : TokenInfo,
+    pub span : Span,
 }
 
 impl Token {
     pub fn new(tag : Tag, info : TokenInfo) -> Self {
         Token {
-            tag : tag,
+            tag,
             info : info,
+            span : Span::default(),
         }
     }
 
-    // Just for debugging purpose.
-    #[allow(dead_code)]
-    pub fn to_cow_string(&self) -> Cow<str> {
+    // Human readable description used in error messages.
+    pub fn describe(&self) -> Cow<'_, str> {
+        match self.tag {
+            Tag::Eof => Cow::Borrowed(self.tag.describe()),
+            // The keyword as written, not the listing form.
+            Tag::Type => {
+                match self.info {
+                    TokenInfo::Int => Cow::Borrowed("`int`"),
+                    TokenInfo::Float => Cow::Borrowed("`float`"),
+                    TokenInfo::Bool => Cow::Borrowed("`bool`"),
+                    _ => panic!("Wrong Type info inside token."),
+                }
+            },
+            _ => Cow::Owned(format!("`{}`", self.to_cow_string())),
+        }
+    }
+
+    pub fn to_cow_string(&self) -> Cow<'_, str> {
         match self.tag {
             Tag::Eof => Cow::Borrowed("EOF"),
-            Tag::Error => Cow::Borrowed("error"), // Parsing error.
+            Tag::Error => {
+                if let TokenInfo::Error(ref err) = self.info {
+                    match *err {
+                        LexError::UnknownChar(c) |
+                        LexError::LoneChar(c) => Cow::Owned(c.to_string()),
+                        LexError::MalformedNumber(ref s) |
+                        LexError::IntTooLarge(ref s) |
+                        LexError::RealOutOfRange(ref s) => Cow::Borrowed(s),
+                        LexError::UnterminatedComment => Cow::Borrowed("/*"),
+                    }
+                } else { panic!("Wrong Error info inside token.") }
+            },
             /* Reserved words. */
             Tag::If => Cow::Borrowed("if"),
             Tag::Else => Cow::Borrowed("else"),
             Tag::While => Cow::Borrowed("while"),
+            Tag::For => Cow::Borrowed("for"),
+            Tag::Do => Cow::Borrowed("do"),
+            Tag::Switch => Cow::Borrowed("switch"),
+            Tag::Case => Cow::Borrowed("case"),
+            Tag::Default => Cow::Borrowed("default"),
             Tag::Break => Cow::Borrowed("break"),
+            Tag::Continue => Cow::Borrowed("continue"),
+            Tag::Return => Cow::Borrowed("return"),
+            Tag::Void => Cow::Borrowed("void"),
+            Tag::Const => Cow::Borrowed("const"),
             /* Separators. */
             Tag::CloseBlock => Cow::Borrowed("}"),
             Tag::OpenBlock => Cow::Borrowed("{"),
             Tag::SemiColon => Cow::Borrowed(";"),
+            Tag::Colon => Cow::Borrowed(":"),
+            Tag::Comma => Cow::Borrowed(","),
             Tag::LArrParen => Cow::Borrowed("["),
             Tag::RArrParen => Cow::Borrowed("]"),
             Tag::LParen => Cow::Borrowed("("),
@@ -120,6 +311,7 @@ impl Token {
                 match self.info {
                     TokenInfo::Int => Cow::Borrowed("type(int)"),
                     TokenInfo::Float => Cow::Borrowed("type(float)"),
+                    TokenInfo::Bool => Cow::Borrowed("type(bool)"),
                     _ => panic!("Wrong Type info inside token."),
                 }
             },
@@ -133,46 +325,165 @@ impl Token {
                     Cow::Owned(format!("{}", x))
                 } else { panic!("Wrong number info inside token.") }
             },
+            Tag::Real => {
+                if let TokenInfo::Real(x) = self.info {
+                    Cow::Owned(format!("{:?}", x))
+                } else { panic!("Wrong real info inside token.") }
+            },
             Tag::True => Cow::Borrowed("True"),
             Tag::False => Cow::Borrowed("False"),
         }
     }
 }
+
+impl fmt::Display for Token {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_cow_string())
+    }
+}
  
 pub struct Scanner {
-    buffer : String,    
+    buffer : String,    // Whole input, never modified.
     lookahead : char,
+    pos  : Position,    // Position of the lookahead.
+    next : Position,    // Position of the next character in the buffer.
+    pub errors : Vec<Diagnostic>,   // Lexical errors found so far.
+    pub comments : Vec<Comment>,    // Comments skipped so far.
 }
 
 impl Scanner {
-    pub fn new() -> Scanner {
+    // Scanner over the standard input.
+    pub fn new() -> io::Result<Scanner> {
+        Scanner::from_reader(io::stdin())
+    }
+
+    pub fn from_file<P : AsRef<Path>>(path : P) -> io::Result<Scanner> {
+        Scanner::from_reader(File::open(path)?)
+    }
+
+    // The whole stream is read upfront. Invalid UTF-8 is an error.
+    pub fn from_reader<R : Read>(mut reader : R) -> io::Result<Scanner> {
         let mut s = String::new();
-        io::stdin().read_line(&mut s).ok();
-        println!("{}", s);
-        Scanner {
-            buffer : s,
-            lookahead : ' ',
-        }
+        reader.read_to_string(&mut s)?;
+        Ok(Scanner::new_static(s))
+    }
+
+    pub fn from_str(s : &str) -> Scanner {
+        Scanner::new_static(String::from(s))
     }
 
     pub fn new_static(s : String) -> Scanner {
-        println!("{}", s);
         Scanner {
             buffer : s,
             lookahead : ' ',
+            pos  : Position { offset : 0, line : 1, col : 1 },
+            next : Position { offset : 0, line : 1, col : 1 },
+            errors : Vec::new(),
+            comments : Vec::new(),
         }
     }
 
+    // Text being scanned.
+    pub fn source(&self) -> &str {
+        &self.buffer
+    }
+
     /* Scan the input until it finds a token. */
     pub fn scan(&mut self) -> Token {
+        let tok = match self.skip_trivia() {
+            Some(tok) => tok,
+            None => {
+                let start = self.pos;
+                let mut tok = self.scan_token();
+                tok.span = self.span_from(start);
+                tok
+            },
+        };
+        if let TokenInfo::Error(ref err) = tok.info {
+            self.errors.push(err.to_diagnostic(tok.span));
+        }
+        tok
+    }
+
+    // Skip spaces and comments. Returns an error token if a block comment
+    // is not terminated.
+    fn skip_trivia(&mut self) -> Option<Token> {
+        loop {
+            match self.lookahead {
+                ' '|'\t'|'\n'|'\r' => {
+                    /* Skip spaces. Read new character. */
+                    self.lookahead = self.read_char();
+                },
+                '/' if self.peek_char() == '/' => self.skip_line_comment(),
+                '/' if self.peek_char() == '*' => {
+                    if let Some(tok) = self.skip_block_comment() {
+                        return Some(tok);
+                    }
+                },
+                _ => return None,
+            }
+        }
+    }
+
+    fn skip_line_comment(&mut self) {
+        let start = self.pos;
+        let mut text = String::new();
+        while self.lookahead != '\n' && self.lookahead != '\0' {
+            text.push(self.lookahead);
+            self.lookahead = self.read_char();
+        }
+        let span = self.span_from(start);
+        self.comments.push(Comment { kind : CommentKind::Line, text, span });
+    }
+
+    // Block comments can be nested.
+    fn skip_block_comment(&mut self) -> Option<Token> {
+        let start = self.pos;
+        let mut text = String::new();
+        let mut depth = 0;
+        loop {
+            match (self.lookahead, self.peek_char()) {
+                ('\0', _) => {
+                    let mut tok = self.error(LexError::UnterminatedComment);
+                    tok.span = self.span_from(start);
+                    return Some(tok);
+                },
+                ('/', '*') | ('*', '/') => {
+                    depth += if self.lookahead == '/' { 1 } else { -1 };
+                    text.push(self.lookahead);
+                    self.lookahead = self.read_char();
+                    text.push(self.lookahead);
+                    self.lookahead = self.read_char();
+                    if depth == 0 {
+                        break;
+                    }
+                },
+                (c, _) => {
+                    text.push(c);
+                    self.lookahead = self.read_char();
+                },
+            }
+        }
+        let span = self.span_from(start);
+        self.comments.push(Comment { kind : CommentKind::Block, text, span });
+        None
+    }
+
+    // Span from start to the lookahead, excluded.
+    fn span_from(&self, start : Position) -> Span {
+        Span {
+            lo   : start.offset,
+            hi   : self.pos.offset,
+            line : start.line,
+            col  : start.col,
+        }
+    }
+
+    fn scan_token(&mut self) -> Token {
         match self.lookahead {
             '\0' => Token::new(Tag::Eof, TokenInfo::NoInfo),
-            ' '|'\t'|'\n' => {
-                /* Skip spaces. Read new character. */
-                self.lookahead = self.read_char();
-                self.scan()
-            },
             '0' ... '9' => self.scan_number(),
+            '.' if self.peek_char().is_ascii_digit() => self.scan_number(),
             'a' ... 'z' |
             'A' ... 'Z' => self.scan_iden_keyword(),
             '='|'!'|'<'|'>' => self.scan_relop(),
@@ -188,24 +499,73 @@ impl Scanner {
             '{' => self.single_token(Token::new(Tag::OpenBlock, TokenInfo::NoInfo)),
             '}' => self.single_token(Token::new(Tag::CloseBlock, TokenInfo::NoInfo)),
             ';' => self.single_token(Token::new(Tag::SemiColon, TokenInfo::NoInfo)),
-            _ => Token::new(Tag::Eof, TokenInfo::NoInfo), /* ERRORE!!! */
+            ',' => self.single_token(Token::new(Tag::Comma, TokenInfo::NoInfo)),
+            ':' => self.single_token(Token::new(Tag::Colon, TokenInfo::NoInfo)),
+            c => {
+                let tok = self.error(LexError::UnknownChar(c));
+                self.single_token(tok)
+            },
         }
     }
 
+    // Error token. Diagnostics are recorded once the span is known.
+    fn error(&self, err : LexError) -> Token {
+        Token::new(Tag::Error, TokenInfo::Error(err))
+    }
+
     fn single_token(&mut self, tok : Token) -> Token {
-        self.lookahead = ' ';
+        self.lookahead = self.read_char();
         tok
     }
 
+    // num -> digits
+    // real -> digits . digits exp | . digits exp | digits exp
     fn scan_number(&mut self) -> Token {
-        let mut v = 0;
+        let mut s = String::new();
+        let mut real = false;
+        self.scan_digits(&mut s);
+        if self.lookahead == '.' {
+            real = true;
+            s.push('.');
+            self.lookahead = self.read_char();
+            self.scan_digits(&mut s);
+        }
+        if self.lookahead == 'e' || self.lookahead == 'E' {
+            // exp -> e digits | e+ digits | e- digits
+            real = true;
+            s.push(self.lookahead);
+            self.lookahead = self.read_char();
+            if self.lookahead == '+' || self.lookahead == '-' {
+                s.push(self.lookahead);
+                self.lookahead = self.read_char();
+            }
+            if !self.lookahead.is_ascii_digit() {
+                return self.error(LexError::MalformedNumber(s));
+            }
+            self.scan_digits(&mut s);
+        }
+        if real {
+            // The text scanned is always a valid float literal, but it
+            // may be too large to be finite.
+            let v : f64 = s.parse().unwrap();
+            if v.is_finite() {
+                Token::new(Tag::Real, TokenInfo::Real(v))
+            } else {
+                self.error(LexError::RealOutOfRange(s))
+            }
+        } else {
+            match s.parse() {
+                Ok(v) => Token::new(Tag::Num, TokenInfo::Num(v)),
+                Err(_) => self.error(LexError::IntTooLarge(s)),
+            }
+        }
+    }
+
+    fn scan_digits(&mut self, s : &mut String) {
         while self.lookahead.is_digit(10) {
-            v = v*10;
-            v += self.lookahead.to_digit(10).unwrap();
+            s.push(self.lookahead);
             self.lookahead = self.read_char();
         }
-        /* TODO: check for float. */
-        Token::new(Tag::Num, TokenInfo::Num(v))
     }
 
     //TODO: really broken too much copies, inefficient.
@@ -226,8 +586,26 @@ impl Scanner {
             Token::new(Tag::Else, TokenInfo::NoInfo)
         } else if x == "while".as_bytes() {
             Token::new(Tag::While, TokenInfo::NoInfo)
+        } else if x == "for".as_bytes() {
+            Token::new(Tag::For, TokenInfo::NoInfo)
+        } else if x == "do".as_bytes() {
+            Token::new(Tag::Do, TokenInfo::NoInfo)
+        } else if x == "switch".as_bytes() {
+            Token::new(Tag::Switch, TokenInfo::NoInfo)
+        } else if x == "case".as_bytes() {
+            Token::new(Tag::Case, TokenInfo::NoInfo)
+        } else if x == "default".as_bytes() {
+            Token::new(Tag::Default, TokenInfo::NoInfo)
         } else if x == "break".as_bytes() {
             Token::new(Tag::Break, TokenInfo::NoInfo)
+        } else if x == "continue".as_bytes() {
+            Token::new(Tag::Continue, TokenInfo::NoInfo)
+        } else if x == "return".as_bytes() {
+            Token::new(Tag::Return, TokenInfo::NoInfo)
+        } else if x == "void".as_bytes() {
+            Token::new(Tag::Void, TokenInfo::NoInfo)
+        } else if x == "const".as_bytes() {
+            Token::new(Tag::Const, TokenInfo::NoInfo)
         } else if x == "True".as_bytes() {
             Token::new(Tag::True, TokenInfo::NoInfo)
         } else if x == "False".as_bytes() {
@@ -236,6 +614,8 @@ impl Scanner {
             Token::new(Tag::Type, TokenInfo::Int)
         } else if x == "float".as_bytes() {
             Token::new(Tag::Type, TokenInfo::Float)
+        } else if x == "bool".as_bytes() {
+            Token::new(Tag::Type, TokenInfo::Bool)
         } else {
             Token::new(Tag::Ide, TokenInfo::Ide(s1))
         }
@@ -263,15 +643,56 @@ impl Scanner {
         match (c, self.lookahead) {
             ('|', '|') => self.single_token(Token::new(Tag::BoolOp, TokenInfo::Or)),
             ('&', '&') => self.single_token(Token::new(Tag::BoolOp, TokenInfo::And)),
-            _          => Token::new(Tag::Error, TokenInfo::NoInfo),
+            _          => self.error(LexError::LoneChar(c)),
         }
     }
 
+    // Character after the lookahead.
+    fn peek_char(&self) -> char {
+        self.buffer[self.next.offset..].chars().next().unwrap_or('\0')
+    }
+
     fn read_char(&mut self) -> char {
-        if self.buffer.len() == 0 {
+        self.pos = self.next;
+        if self.next.offset == self.buffer.len() {
             '\0'
         } else {
-            self.buffer.remove(0)
+            let c = self.peek_char();
+            self.next.offset += c.len_utf8();
+            if c == '\n' {
+                self.next.line += 1;
+                self.next.col = 1;
+            } else {
+                self.next.col += 1;
+            }
+            c
+        }
+    }
+}
+
+// Tokens up to the end of the input, EOF excluded.
+// Implemented on `&mut Scanner` so that it does not shadow the inherent
+// `scan` with `Iterator::scan`.
+impl Iterator for &mut Scanner {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = Scanner::scan(self);
+        if tok.tag == Tag::Eof {
+            None
+        } else {
+            Some(tok)
         }
     }
 }
+
+// One line for each token with its position, tag and text.
+pub fn token_listing(mut scanner : Scanner) -> String {
+    let mut out = String::new();
+    for tok in &mut scanner {
+        let pos = format!("{}:{}", tok.span.line, tok.span.col);
+        let tag = format!("{:?}", tok.tag);
+        out.push_str(&format!("{:<8} {:<11} {}\n", pos, tag, tok));
+    }
+    out
+}
@@ -0,0 +1,577 @@
+/*
+    Lints: warnings about programs that are valid but probably wrong. Each
+    lint has a name and a level, allow, warn or deny, that can be changed
+    from the command line. Lints report warnings whose code is the name of
+    the lint, the levels decide which ones are kept and which are errors.
+*/
+use std::collections::{HashMap, HashSet};
+use ast::statement::{Program, Block, Statement, StatementKind, DeclId, covers_all};
+use ast::expression::{BoolExpr, BoolExprKind, NumExpr, NumExprKind, Relop, Loc, LocKind};
+use ast::visit::{Visitor, walk_block, walk_statement, walk_loc};
+use resolver::{DeclInfo, DeclKind};
+use diagnostics::{Diagnostic, Severity};
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny,
+}
+
+pub struct Lint {
+    pub name        : &'static str,
+    pub default     : Level,
+    pub description : &'static str,
+}
+
+pub static UNINITIALIZED : Lint = Lint {
+    name : "uninitialized",
+    default : Level::Warn,
+    description : "variables read before they are surely assigned",
+};
+
+pub static UNUSED_VARIABLES : Lint = Lint {
+    name : "unused-variables",
+    default : Level::Warn,
+    description : "variables declared and never read",
+};
+
+pub static DEAD_STORES : Lint = Lint {
+    name : "dead-stores",
+    default : Level::Warn,
+    description : "assignments whose value is never read",
+};
+
+pub static UNREACHABLE_CODE : Lint = Lint {
+    name : "unreachable-code",
+    default : Level::Warn,
+    description : "statements after a break or a continue",
+};
+
+pub static WHILE_FALSE : Lint = Lint {
+    name : "while-false",
+    default : Level::Warn,
+    description : "loops whose body never runs",
+};
+
+pub static CONSTANT_CONDITION : Lint = Lint {
+    name : "constant-condition",
+    default : Level::Warn,
+    description : "if conditions that are always true or always false",
+};
+
+pub static MISSING_DEFAULT : Lint = Lint {
+    name : "missing-default",
+    default : Level::Warn,
+    description : "switches that run no case for some values",
+};
+
+pub static LINTS : &[&Lint] = &[
+    &UNINITIALIZED,
+    &UNUSED_VARIABLES,
+    &DEAD_STORES,
+    &UNREACHABLE_CODE,
+    &WHILE_FALSE,
+    &CONSTANT_CONDITION,
+    &MISSING_DEFAULT,
+];
+
+pub struct LintLevels {
+    levels : HashMap<&'static str, Level>,
+}
+
+impl LintLevels {
+    pub fn new() -> Self {
+        LintLevels {
+            levels : LINTS.iter().map(|l| (l.name, l.default)).collect(),
+        }
+    }
+
+    pub fn set(&mut self, name : &str, level : Level) -> Result<(), String> {
+        match LINTS.iter().find(|l| l.name == name) {
+            Some(l) => {
+                self.levels.insert(l.name, level);
+                Ok(())
+            },
+            None => Err(format!("unknown lint `{}`", name)),
+        }
+    }
+
+    pub fn level(&self, name : &str) -> Level {
+        self.levels.get(name).cloned().unwrap_or(Level::Warn)
+    }
+
+    // Drop the allowed lints and turn the denied ones into errors. The
+    // reports of all the lints are sorted by position.
+    pub fn apply(&self, diags : Vec<Diagnostic>) -> Vec<Diagnostic> {
+        let mut out : Vec<Diagnostic> = diags.into_iter()
+            .filter_map(|mut d| {
+                match self.level(d.code.unwrap_or("")) {
+                    Level::Allow => return None,
+                    Level::Warn => d.severity = Severity::Warning,
+                    Level::Deny => d.severity = Severity::Error,
+                }
+                Some(d)
+            })
+            .collect();
+        out.sort_by_key(|d| d.primary.span.lo);
+        out
+    }
+}
+
+// Run all the lints but uninitialized, that has its own pass.
+pub fn check(program : &Program, decls : &[DeclInfo]) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    let mut uses = Uses::new(decls.len());
+    uses.visit_program(program);
+    unused_variables(&uses, decls, &mut diags);
+    let mut live = Liveness {
+        decls,
+        uses : &uses,
+        report : true,
+        loops : Vec::new(),
+        diagnostics : Vec::new(),
+    };
+    for f in program.functions() {
+        live.block(&f.body, HashSet::new());
+    }
+    if let Some(ref b) = program.block {
+        live.block(b, HashSet::new());
+    }
+    diags.append(&mut live.diagnostics);
+    let mut control = ControlFlow { diagnostics : Vec::new() };
+    control.visit_program(program);
+    diags.append(&mut control.diagnostics);
+    diags
+}
+
+/* unused-variables */
+
+// How many times each declaration is read and written.
+struct Uses {
+    reads  : Vec<usize>,
+    writes : Vec<usize>,
+}
+
+impl Uses {
+    fn new(n : usize) -> Self {
+        Uses {
+            reads : vec![0; n],
+            writes : vec![0; n],
+        }
+    }
+}
+
+impl Visitor for Uses {
+    fn visit_statement(&mut self, s : &Statement) {
+        if let StatementKind::Assign(ref l, ref e) = s.kind {
+            // The target is written, only its indices are read.
+            if let Some(id) = l.decl {
+                self.writes[id.0] += 1;
+            }
+            walk_loc(self, l);
+            self.visit_bool_expr(e);
+        } else {
+            walk_statement(self, s);
+        }
+    }
+
+    fn visit_loc(&mut self, l : &Loc) {
+        if let Some(id) = l.decl {
+            self.reads[id.0] += 1;
+        }
+        walk_loc(self, l);
+    }
+}
+
+fn unused_variables(uses : &Uses, decls : &[DeclInfo], diags : &mut Vec<Diagnostic>) {
+    for (i, d) in decls.iter().enumerate() {
+        if uses.reads[i] > 0 {
+            continue;
+        }
+        let msg = if d.kind == DeclKind::Const {
+            format!("unused constant `{}`", d.name)
+        } else if uses.writes[i] > 0 {
+            format!("variable `{}` is assigned to, but never read", d.name)
+        } else {
+            format!("unused variable `{}`", d.name)
+        };
+        diags.push(Diagnostic::warning(&msg, d.span)
+            .with_code(UNUSED_VARIABLES.name)
+            .with_primary_label("declared here"));
+    }
+}
+
+/* dead-stores */
+
+// Backward liveness of the variables. A store is dead when the variable is
+// not live after it. Arrays and globals, that calls may read, are not
+// tracked, and variables never read are left to unused-variables.
+struct Liveness<'a> {
+    decls       : &'a [DeclInfo],
+    uses        : &'a Uses,
+    report      : bool,     // False while looking for the fixpoint of a loop.
+    loops       : Vec<LoopLive>,
+    diagnostics : Vec<Diagnostic>,
+}
+
+type Live = HashSet<DeclId>;
+
+// What is live where break and continue go in an enclosing loop.
+struct LoopLive {
+    label : Option<String>,
+    after : Live,           // After the loop, for break.
+    next  : Live,           // At the condition, or the step of a for.
+}
+
+impl<'a> Liveness<'a> {
+    fn block(&mut self, b : &Block, mut live : Live) -> Live {
+        for s in b.stmts.iter().rev() {
+            live = self.statement(s, live);
+        }
+        live
+    }
+
+    fn statement(&mut self, s : &Statement, live : Live) -> Live {
+        match s.kind {
+            StatementKind::Assign(ref l, ref e) => {
+                let mut live = live;
+                if let Some(id) = l.decl {
+                    if self.tracked(id) {
+                        if self.report && !live.contains(&id) {
+                            let msg = format!("value assigned to `{}` is never read", self.decls[id.0].name);
+                            self.diagnostics.push(Diagnostic::warning(&msg, l.span)
+                                .with_code(DEAD_STORES.name)
+                                .with_primary_label("overwritten or never used after this"));
+                        }
+                        live.remove(&id);
+                    }
+                }
+                add_reads(&mut live, e);
+                if let LocKind::Index(_, ref indices) = l.kind {
+                    for i in indices {
+                        add_reads(&mut live, i);
+                    }
+                }
+                live
+            },
+            StatementKind::If(ref e, ref s1) => {
+                let mut live_in = self.statement(s1, live.clone());
+                live_in.extend(live);
+                add_reads(&mut live_in, e);
+                live_in
+            },
+            StatementKind::IfElse(ref e, ref s1, ref s2) => {
+                let mut live_in = self.statement(s1, live.clone());
+                live_in.extend(self.statement(s2, live));
+                add_reads(&mut live_in, e);
+                live_in
+            },
+            StatementKind::While(..) | StatementKind::For(_) | StatementKind::DoWhile(..) => {
+                self.looping(s, None, live)
+            },
+            StatementKind::Switch(ref e, ref cases) => {
+                let mut live_in = if covers_all(cases) { Live::new() } else { live.clone() };
+                for c in cases {
+                    let mut l = live.clone();
+                    for s in c.stmts.iter().rev() {
+                        l = self.statement(s, l);
+                    }
+                    live_in.extend(l);
+                }
+                add_reads(&mut live_in, e);
+                live_in
+            },
+            StatementKind::Labeled(ref name, ref s1) => self.looping(s1, Some(name.clone()), live),
+            StatementKind::Break(ref label) => self.target(label).after.clone(),
+            StatementKind::Continue(ref label) => self.target(label).next.clone(),
+            // Nothing is read after a return.
+            StatementKind::Return(ref e) => {
+                let mut live = Live::new();
+                if let Some(ref e) = *e {
+                    add_reads(&mut live, e);
+                }
+                live
+            },
+            StatementKind::Call(_, ref args) => {
+                let mut live = live;
+                for e in args {
+                    add_reads(&mut live, e);
+                }
+                live
+            },
+            StatementKind::BlockStmt(ref b) => self.block(b, live),
+            StatementKind::Error => live,
+        }
+    }
+
+    // What is live where the next iteration starts depends on the body, and
+    // the body on what is live there. Look for the fixpoint, then go through
+    // the loop once more to report.
+    fn looping(&mut self, s : &Statement, label : Option<String>, live : Live) -> Live {
+        let report = self.report;
+        self.report = false;
+        self.loops.push(LoopLive { label, after : live.clone(), next : Live::new() });
+        loop {
+            let (next, _) = self.iteration(s, &live);
+            if next == self.loops.last().unwrap().next {
+                break;
+            }
+            self.loops.last_mut().unwrap().next = next;
+        }
+        self.report = report;
+        let (_, entry) = self.iteration(s, &live);
+        self.loops.pop();
+        entry
+    }
+
+    // One pass through the loop from what is live at the start of the next
+    // iteration. Returns what is live there again, and before the loop.
+    fn iteration(&mut self, s : &Statement, live : &Live) -> (Live, Live) {
+        let next = self.loops.last().unwrap().next.clone();
+        match s.kind {
+            StatementKind::While(ref e, ref s1) => {
+                let mut head = self.statement(s1, next);
+                head.extend(live.iter().cloned());
+                add_reads(&mut head, e);
+                (head.clone(), head)
+            },
+            StatementKind::For(ref f) => {
+                let mut head = self.statement(&f.body, next);
+                if let Some(ref e) = f.cond {
+                    head.extend(live.iter().cloned());
+                    add_reads(&mut head, e);
+                }
+                let step = match f.step {
+                    Some(ref step) => self.statement(step, head.clone()),
+                    None => head.clone(),
+                };
+                let entry = match f.init {
+                    Some(ref init) => self.statement(init, head),
+                    None => head,
+                };
+                (step, entry)
+            },
+            StatementKind::DoWhile(ref s1, ref e) => {
+                let entry = self.statement(s1, next);
+                let mut cond = entry.clone();
+                cond.extend(live.iter().cloned());
+                add_reads(&mut cond, e);
+                (cond, entry)
+            },
+            _ => unreachable!("Label on a statement that is not a loop."),
+        }
+    }
+
+    // The loop a break or continue goes to; the resolver checked it exists.
+    fn target(&self, label : &Option<String>) -> &LoopLive {
+        match *label {
+            Some(_) => self.loops.iter().rev().find(|l| l.label == *label).unwrap(),
+            None => self.loops.last().unwrap(),
+        }
+    }
+
+    fn tracked(&self, id : DeclId) -> bool {
+        let d = &self.decls[id.0];
+        let local = d.kind == DeclKind::Local || d.kind == DeclKind::Param;
+        local && d.type_id.dim_width.is_empty() && self.uses.reads[id.0] > 0
+    }
+}
+
+fn add_reads(live : &mut Live, e : &BoolExpr) {
+    struct Reads<'a>(&'a mut Live);
+    impl<'a> Visitor for Reads<'a> {
+        fn visit_loc(&mut self, l : &Loc) {
+            if let Some(id) = l.decl {
+                self.0.insert(id);
+            }
+            walk_loc(self, l);
+        }
+    }
+    Reads(live).visit_bool_expr(e);
+}
+
+/* unreachable-code, while-false, constant-condition and missing-default */
+
+struct ControlFlow {
+    diagnostics : Vec<Diagnostic>,
+}
+
+impl ControlFlow {
+    // Statements after the first one that diverges, in a block or a case.
+    fn unreachable(&mut self, stmts : &[Box<Statement>]) {
+        let n = stmts.len();
+        if let Some(i) = stmts.iter().position(|s| diverges(s)) {
+            if i + 1 < n {
+                let mut span = stmts[i + 1].span;
+                span.hi = stmts[n - 1].span.hi;
+                let label = match stmts[i].kind {
+                    StatementKind::Break(_) => "any code following this `break` is unreachable",
+                    StatementKind::Continue(_) => "any code following this `continue` is unreachable",
+                    StatementKind::Return(_) => "any code following this `return` is unreachable",
+                    _ => "any code following this statement is unreachable",
+                };
+                self.diagnostics.push(Diagnostic::warning("unreachable statement", span)
+                    .with_code(UNREACHABLE_CODE.name)
+                    .with_primary_label("unreachable statement")
+                    .with_label(stmts[i].span, label));
+            }
+        }
+    }
+
+    fn never_runs(&mut self, e : &BoolExpr) {
+        if const_bool(e) == Some(false) {
+            self.diagnostics.push(Diagnostic::warning("the body of this loop never runs", e.span)
+                .with_code(WHILE_FALSE.name)
+                .with_primary_label("this condition is always false"));
+        }
+    }
+}
+
+impl Visitor for ControlFlow {
+    fn visit_block(&mut self, b : &Block) {
+        self.unreachable(&b.stmts);
+        walk_block(self, b);
+    }
+
+    fn visit_statement(&mut self, s : &Statement) {
+        match s.kind {
+            StatementKind::While(ref e, _) => self.never_runs(e),
+            StatementKind::Switch(ref e, ref cases) => {
+                for c in cases {
+                    self.unreachable(&c.stmts);
+                }
+                if !covers_all(cases) {
+                    self.diagnostics.push(Diagnostic::warning("`switch` without a `default` case", e.span)
+                        .with_code(MISSING_DEFAULT.name)
+                        .with_primary_label("no case runs for the other values"));
+                }
+            },
+            StatementKind::For(ref f) => {
+                if let Some(ref e) = f.cond {
+                    self.never_runs(e);
+                }
+            },
+            StatementKind::If(ref e, ref s1) => {
+                if let Some(v) = const_bool(e) {
+                    let mut d = constant_condition(e, v);
+                    if !v {
+                        d = d.with_label(s1.span, "never executed");
+                    }
+                    self.diagnostics.push(d);
+                }
+            },
+            StatementKind::IfElse(ref e, ref s1, ref s2) => {
+                if let Some(v) = const_bool(e) {
+                    let never = if v { s2.span } else { s1.span };
+                    let d = constant_condition(e, v).with_label(never, "never executed");
+                    self.diagnostics.push(d);
+                }
+            },
+            _ => (),
+        }
+        walk_statement(self, s);
+    }
+}
+
+fn constant_condition(e : &BoolExpr, v : bool) -> Diagnostic {
+    let msg = format!("this condition is always {}", v);
+    Diagnostic::warning(&msg, e.span)
+        .with_code(CONSTANT_CONDITION.name)
+        .with_primary_label(&format!("always {}", v))
+}
+
+// True if the statement always ends with a break, a continue or a return.
+fn diverges(s : &Statement) -> bool {
+    match s.kind {
+        StatementKind::Break(_) | StatementKind::Continue(_) | StatementKind::Return(_) => true,
+        StatementKind::BlockStmt(ref b) => b.stmts.iter().any(|s| diverges(s)),
+        StatementKind::IfElse(_, ref s1, ref s2) => diverges(s1) && diverges(s2),
+        StatementKind::Switch(_, ref cases) => {
+            covers_all(cases) && cases.iter().all(|c| c.stmts.iter().any(|s| diverges(s)))
+        },
+        _ => false,
+    }
+}
+
+/* Constant expressions. */
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum Const {
+    Int(i32),       // Wraps around on overflow, as at run time.
+    Float(f64),
+    Bool(bool),
+}
+
+impl Const {
+    fn as_float(self) -> Option<f64> {
+        match self {
+            Const::Int(x) => Some(x as f64),
+            Const::Float(x) => Some(x),
+            Const::Bool(_) => None,
+        }
+    }
+}
+
+fn const_bool(e : &BoolExpr) -> Option<bool> {
+    match const_bool_expr(e) {
+        Some(Const::Bool(b)) => Some(b),
+        _ => None,
+    }
+}
+
+fn const_bool_expr(e : &BoolExpr) -> Option<Const> {
+    match e.kind {
+        BoolExprKind::Or(ref a, ref b) => Some(Const::Bool(const_bool(a)? || const_bool(b)?)),
+        BoolExprKind::And(ref a, ref b) => Some(Const::Bool(const_bool(a)? && const_bool(b)?)),
+        BoolExprKind::Eq(ref a, ref b) | BoolExprKind::Neq(ref a, ref b) => {
+            let eq = match (const_bool_expr(a)?, const_bool_expr(b)?) {
+                (Const::Bool(x), Const::Bool(y)) => x == y,
+                (x, y) => x.as_float()? == y.as_float()?,
+            };
+            let neq = matches!(e.kind, BoolExprKind::Neq(..));
+            Some(Const::Bool(eq != neq))
+        },
+        BoolExprKind::Relop(ref op, ref a, ref b) => {
+            let x = const_num_expr(a)?.as_float()?;
+            let y = const_num_expr(b)?.as_float()?;
+            Some(Const::Bool(match *op {
+                Relop::Ge => x >= y,
+                Relop::Gr => x > y,
+                Relop::Leq => x <= y,
+                Relop::Les => x < y,
+            }))
+        },
+        BoolExprKind::NumExpr(ref a) => const_num_expr(a),
+    }
+}
+
+fn const_num_expr(e : &NumExpr) -> Option<Const> {
+    let arith = |a : &NumExpr, b : &NumExpr, int : fn(i32, i32) -> Option<i32>, float : fn(f64, f64) -> f64| {
+        match (const_num_expr(a)?, const_num_expr(b)?) {
+            (Const::Int(x), Const::Int(y)) => int(x, y).map(Const::Int),
+            (x, y) => Some(Const::Float(float(x.as_float()?, y.as_float()?))),
+        }
+    };
+    match e.kind {
+        NumExprKind::Add(ref a, ref b) => arith(a, b, |x, y| Some(x.wrapping_add(y)), |x, y| x + y),
+        NumExprKind::Sub(ref a, ref b) => arith(a, b, |x, y| Some(x.wrapping_sub(y)), |x, y| x - y),
+        NumExprKind::Mul(ref a, ref b) => arith(a, b, |x, y| Some(x.wrapping_mul(y)), |x, y| x * y),
+        NumExprKind::Div(ref a, ref b) => arith(a, b, |x, y| x.checked_div(y), |x, y| x / y),
+        NumExprKind::Not(ref a) => match const_num_expr(a)? {
+            Const::Bool(b) => Some(Const::Bool(!b)),
+            _ => None,
+        },
+        NumExprKind::Minus(ref a) => match const_num_expr(a)? {
+            Const::Int(x) => Some(Const::Int(x.wrapping_neg())),
+            Const::Float(x) => Some(Const::Float(-x)),
+            Const::Bool(_) => None,
+        },
+        NumExprKind::Expr(ref b) => const_bool_expr(b),
+        NumExprKind::Loc(_) | NumExprKind::Call(..) => None,
+        NumExprKind::Num(x) => Some(Const::Int(x as i32)),
+        NumExprKind::Real(x) => Some(Const::Float(x)),
+        NumExprKind::True => Some(Const::Bool(true)),
+        NumExprKind::False => Some(Const::Bool(false)),
+    }
+}
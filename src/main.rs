@@ -5,15 +5,214 @@ mod symbol_table;
 mod code_generator;
 mod interpreter;
 mod ast;
+mod diagnostics;
+mod cst;
+mod formatter;
+mod cfg;
+mod resolver;
+mod typeck;
+mod uninit;
+mod lint;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::process;
+
+// What the compiler prints instead of running the program.
+#[derive(PartialEq)]
+enum Emit {
+    Run,
+    Tokens,
+    Cst,
+    AstJson,
+    Sexp,
+    AstDot,
+    CfgDot,
+}
+
+struct Options {
+    emit        : Emit,
+    path        : Option<String>,
+    lints       : lint::LintLevels,
+}
+
+fn usage() -> ! {
+    eprintln!("usage: mini_compiler [--emit=tokens|cst|ast-json|sexp|ast-dot|cfg-dot] [-A|-W|-D LINT]... [FILE]");
+    eprintln!("       mini_compiler fmt [--check] [FILE...]");
+    process::exit(2);
+}
+
+fn parse_args() -> Options {
+    let mut opts = Options {
+        emit : Emit::Run,
+        path : None,
+        lints : lint::LintLevels::new(),
+    };
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if let Some(emit) = arg.strip_prefix("--emit=") {
+            opts.emit = match emit {
+                "tokens" => Emit::Tokens,
+                "cst" => Emit::Cst,
+                "ast-json" => Emit::AstJson,
+                "sexp" => Emit::Sexp,
+                "ast-dot" => Emit::AstDot,
+                "cfg-dot" => Emit::CfgDot,
+                _ => usage(),
+            };
+        } else if arg == "-A" || arg == "-W" || arg == "-D" {
+            let level = match arg.as_str() {
+                "-A" => lint::Level::Allow,
+                "-W" => lint::Level::Warn,
+                _ => lint::Level::Deny,
+            };
+            let name = args.next().unwrap_or_else(|| usage());
+            if let Err(msg) = opts.lints.set(&name, level) {
+                eprintln!("error: {}", msg);
+                eprintln!("the lints are:");
+                for l in lint::LINTS {
+                    eprintln!("    {:<20} {}", l.name, l.description);
+                }
+                process::exit(2);
+            }
+        } else if arg.starts_with("-") || opts.path.is_some() {
+            usage();
+        } else {
+            opts.path = Some(arg);
+        }
+    }
+    opts
+}
+
+// Format the given files in place, or the standard input to the standard
+// output. With --check nothing is written, and the exit code tells whether
+// all the inputs are already formatted.
+fn run_fmt(args : Vec<String>) {
+    let check = args.iter().any(|a| a == "--check");
+    let paths : Vec<&String> = args.iter().filter(|a| *a != "--check").collect();
+    if paths.iter().any(|p| p.starts_with("-")) {
+        usage();
+    }
+    let mut ok = true;
+    let inputs : Vec<Option<&str>> = if paths.is_empty() {
+        vec![None]
+    } else {
+        paths.iter().map(|p| Some(p.as_str())).collect()
+    };
+    for path in inputs {
+        let name = path.unwrap_or("<stdin>");
+        let mut text = String::new();
+        let res = match path {
+            Some(p) => fs::File::open(p).and_then(|mut f| f.read_to_string(&mut text)),
+            None => io::stdin().read_to_string(&mut text),
+        };
+        if let Err(e) = res {
+            eprintln!("error: cannot read {}: {}", name, e);
+            ok = false;
+            continue;
+        }
+        let formatted = match formatter::format_source(lexer::Scanner::from_str(&text)) {
+            Ok(s) => s,
+            Err(errors) => {
+                let file = diagnostics::SourceFile::new(name, &text);
+                for e in errors {
+                    eprintln!("{}", e.render(&file));
+                }
+                ok = false;
+                continue;
+            },
+        };
+        if check {
+            if formatted != text {
+                println!("{} is not formatted", name);
+                ok = false;
+            }
+        } else if path.is_none() {
+            print!("{}", formatted);
+        } else if formatted != text {
+            if let Err(e) = fs::write(name, formatted) {
+                eprintln!("error: cannot write {}: {}", name, e);
+                ok = false;
+            }
+        }
+    }
+    if !ok {
+        process::exit(1);
+    }
+}
 
 fn main() {
-    println!("Mini Compiler.");
+    if env::args().nth(1).is_some_and(|a| a == "fmt") {
+        run_fmt(env::args().skip(2).collect());
+        return;
+    }
+    let opts = parse_args();
+
+    // Read the program from the file given as argument, or from stdin.
+    let (name, scanner) = match opts.path {
+        Some(ref p) => (p.as_str(), lexer::Scanner::from_file(p)),
+        None => ("<stdin>", lexer::Scanner::new()),
+    };
+    let scanner = match scanner {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("error: cannot read {}: {}", name, e);
+            process::exit(1);
+        },
+    };
+    if opts.emit == Emit::Tokens {
+        print!("{}", lexer::token_listing(scanner));
+        return;
+    }
+    if opts.emit == Emit::Cst {
+        print!("{}", cst::parse(scanner).0.dump());
+        return;
+    }
+
+    let file = diagnostics::SourceFile::new(name, scanner.source());
+    let mut parser = parser::Parser::new(scanner);
+    if opts.emit == Emit::AstJson || opts.emit == Emit::Sexp || opts.emit == Emit::AstDot {
+        let program = match parser.parse_program() {
+            Ok(p) => p,
+            Err(errors) => {
+                for e in errors {
+                    eprintln!("{}", e.render(&file));
+                }
+                process::exit(1);
+            },
+        };
+        match opts.emit {
+            Emit::AstJson => println!("{}", ast::serialize::to_json(&program)),
+            Emit::Sexp => println!("{}", ast::serialize::to_sexp(&program)),
+            _ => print!("{}", ast::serialize::to_dot(&program)),
+        }
+        return;
+    }
 
-    let scanner = lexer::Scanner::new_static("{}".to_string());
-    let parser = parser::Parser::new(scanner);
     let mut code_gen = code_generator::CodeGenerator::new(parser);
-    code_gen.generate_code();
-    
+    code_gen.lints = opts.lints;
+    if let Err(errors) = code_gen.generate_code() {
+        for e in errors {
+            eprintln!("{}", e.render(&file));
+        }
+        process::exit(1);
+    }
+    // Warnings.
+    for d in &code_gen.diagnostics {
+        eprintln!("{}", d.render(&file));
+    }
+    if opts.emit == Emit::CfgDot {
+        print!("{}", cfg::Cfg::new(&code_gen.code).to_dot(&code_gen.code));
+        return;
+    }
+
+    println!("Mini Compiler.");
     let mut inter = interpreter::Interpreter::new(code_gen.code);
-    inter.execute();        
+    if let Err(e) = inter.execute() {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+    inter.dump_variables();
 }
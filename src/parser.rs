@@ -1,50 +1,295 @@
 /*
     Parser for imperative language.
 */
-use lexer::{Scanner, Token, Tag, TokenInfo};
+use lexer::{Scanner, Token, Tag, TokenInfo, Span};
 use ast::statement::Program;
 use std;
+use std::fmt;
+use std::error::Error;
+use diagnostics::{Diagnostic, E_SYNTAX};
+use cst::{TreeBuilder, NodeKind, Checkpoint, SyntaxNode};
+
+// Syntax error found while parsing: the parser expected something
+// different from the token found at the given location, or the token
+// was the right kind but its value is not allowed there.
+#[derive(PartialEq, Debug, Clone)]
+pub struct ParseError {
+    pub expected : &'static str,
+    pub found    : Token,
+    pub span     : Span,
+    pub message  : Option<&'static str>,  // Replaces "expected ..., found ...".
+}
+
+impl ParseError {
+    pub fn new(expected : &'static str, found : Token) -> Self {
+        ParseError {
+            expected,
+            span     : found.span,
+            found,
+            message  : None,
+        }
+    }
+
+    // Error on a token of the expected kind, with a value not allowed.
+    pub fn invalid(message : &'static str, found : Token, span : Span) -> Self {
+        ParseError {
+            expected : "",
+            found,
+            span,
+            message  : Some(message),
+        }
+    }
+
+    fn message(&self) -> String {
+        match self.message {
+            Some(msg) => String::from(msg),
+            None => format!("expected {}, found {}", self.expected, self.found.describe()),
+        }
+    }
+}
+
+impl ParseError {
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let label = match self.message {
+            Some(msg) => String::from(msg),
+            None => format!("expected {}", self.expected),
+        };
+        Diagnostic::error(&self.message(), self.span)
+            .with_code(E_SYNTAX)
+            .with_primary_label(&label)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.span.line, self.span.col, self.message())
+    }
+}
+
+impl Error for ParseError {}
 
 pub struct Parser {
     pub lookahead : Token,
     pub scanner : Scanner,
     pub ast_root : Option<Box<Program>>,
+    pub errors : Vec<ParseError>,   // Syntax errors recovered so far.
+    prev_span : Span,   // Span of the last token shifted.
+    cst : Option<TreeBuilder>,  // Concrete syntax tree, when requested.
+    peeked : Option<Token>,     // Token after the lookahead, if already read.
 }
 
 pub trait ParseNode {
-    fn parse(& mut Parser) -> Box<Self>;
+    fn parse(parser : &mut Parser) -> Result<Box<Self>, ParseError>;
 }
 
 
 impl Parser {
-    pub fn new(mut scanner : Scanner) -> Self {
-        Parser {
-            lookahead : scanner.scan(),
+    pub fn new(scanner : Scanner) -> Self {
+        Parser::build(scanner, None)
+    }
+
+    // Parser that also builds the concrete syntax tree.
+    pub fn with_cst(scanner : Scanner) -> Self {
+        Parser::build(scanner, Some(TreeBuilder::new()))
+    }
+
+    fn build(scanner : Scanner, cst : Option<TreeBuilder>) -> Self {
+        let mut parser = Parser {
+            lookahead : Token::new(Tag::Eof, TokenInfo::NoInfo),
             scanner : scanner,
             ast_root : None,
-        }
+            errors : Vec::new(),
+            prev_span : Span::default(),
+            cst,
+            peeked : None,
+        };
+        parser.lookahead = parser.next_token();
+        parser
     }
 
-    pub fn parse(&mut self) {
-        self.ast_root = Some(Program::parse(self));
+    // Parse the whole program. The tree is built even when there are syntax
+    // errors, with Error nodes in place of the statements that were skipped.
+    pub fn parse(&mut self) -> Result<(), Vec<ParseError>> {
+        self.start_node(NodeKind::File);
+        match Program::parse(self) {
+            Ok(root) => self.ast_root = Some(root),
+            Err(e) => self.errors.push(e),
+        }
+        self.unwind_nodes(1);
+        if let Err(e) = self.match_lookahead(Tag::Eof) {
+            self.errors.push(e);
+            if self.cst.is_some() {
+                // Keep the rest of the input in the tree.
+                self.start_node(NodeKind::Error);
+                while self.lookahead.tag != Tag::Eof {
+                    self.shift_lookahead();
+                }
+                self.finish_node();
+                self.shift_lookahead();
+            }
+        }
+        self.finish_node();
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(std::mem::take(&mut self.errors))
+        }
     }    
     
+    // Parse the whole program. Lexical and syntax errors are returned
+    // together, sorted by position.
+    pub fn parse_program(&mut self) -> Result<Box<Program>, Vec<Diagnostic>> {
+        let res = self.parse();
+        let mut errors = std::mem::take(&mut self.scanner.errors);
+        if let Err(parse_errors) = res {
+            errors.extend(parse_errors.iter().map(|e| e.to_diagnostic()));
+        }
+        if !errors.is_empty() {
+            errors.sort_by_key(|d| d.primary.span.lo);
+            return Err(errors);
+        }
+        Ok(self.get_root())
+    }
+
     pub fn get_root(&mut self) -> Box<Program>{
         std::mem::replace(&mut self.ast_root, None).unwrap()
     }
 
-    pub fn match_lookahead(&mut self, tag : Tag) -> Token {
+    pub fn take_cst(&mut self) -> Option<SyntaxNode> {
+        self.cst.take().map(|b| b.finish())
+    }
+
+    pub fn match_lookahead(&mut self, tag : Tag) -> Result<Token, ParseError> {
         if self.lookahead.tag == tag {
-            self.shift_lookahead()
+            Ok(self.shift_lookahead())
         } else {
-            panic!("Expected {:?}. Found: {:?}", tag, (&self.lookahead.tag, &self.lookahead.info))
+            Err(self.error(tag.describe()))
         }
     }
 
+    // Error for an unexpected lookahead.
+    pub fn error(&self, expected : &'static str) -> ParseError {
+        ParseError::new(expected, self.lookahead.clone())
+    }
+
     pub fn shift_lookahead(&mut self) -> Token {
         let x = Token::new(Tag::Error, TokenInfo::NoInfo);
         let out = std::mem::replace(&mut self.lookahead, x);
-        self.lookahead = self.scanner.scan();
+        if let Some(ref mut b) = self.cst {
+            b.token(out.clone(), &self.scanner);
+        }
+        self.lookahead = self.next_token();
+        self.prev_span = out.span;
         out
     }
+
+    // Token after the lookahead, to tell apart statements starting with the
+    // same token.
+    pub fn peek(&mut self) -> &Token {
+        if self.peeked.is_none() {
+            let tok = self.next_token();
+            self.peeked = Some(tok);
+        }
+        self.peeked.as_ref().unwrap()
+    }
+
+    // Error tokens are skipped: the scanner has already reported them.
+    fn next_token(&mut self) -> Token {
+        if let Some(tok) = self.peeked.take() {
+            return tok;
+        }
+        let mut tok = self.scanner.scan();
+        while tok.tag == Tag::Error {
+            if let Some(ref mut b) = self.cst {
+                b.skip(tok);
+            }
+            tok = self.scanner.scan();
+        }
+        tok
+    }
+
+    // Panic mode recovery: skip tokens until the end of the current statement
+    // or the beginning of a new one.
+    pub fn synchronize(&mut self) {
+        loop {
+            match self.lookahead.tag {
+                Tag::SemiColon => {
+                    self.shift_lookahead();
+                    return;
+                },
+                Tag::Eof | Tag::CloseBlock | Tag::OpenBlock |
+                Tag::If | Tag::While | Tag::For | Tag::Do | Tag::Switch | Tag::Break | Tag::Continue |
+                Tag::Return | Tag::Type | Tag::Const => return,
+                _ => { self.shift_lookahead(); },
+            }
+        }
+    }
+
+    // Same for globals and functions: skip to the end of the item, a `;` or
+    // a whole block, or to the beginning of the next one.
+    pub fn synchronize_item(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.lookahead.tag {
+                Tag::Eof => return,
+                Tag::Type | Tag::Void | Tag::Const if depth == 0 => return,
+                Tag::SemiColon if depth == 0 => {
+                    self.shift_lookahead();
+                    return;
+                },
+                Tag::OpenBlock => {
+                    depth += 1;
+                    self.shift_lookahead();
+                },
+                Tag::CloseBlock => {
+                    self.shift_lookahead();
+                    if depth <= 1 {
+                        return;
+                    }
+                    depth -= 1;
+                },
+                _ => { self.shift_lookahead(); },
+            }
+        }
+    }
+
+    /* Concrete syntax tree events, ignored unless the tree is requested. */
+
+    pub fn start_node(&mut self, kind : NodeKind) {
+        if let Some(ref mut b) = self.cst {
+            b.start_node(kind);
+        }
+    }
+
+    pub fn start_node_at(&mut self, cp : Option<Checkpoint>, kind : NodeKind) {
+        if let (Some(b), Some(cp)) = (self.cst.as_mut(), cp) {
+            b.start_node_at(cp, kind);
+        }
+    }
+
+    pub fn finish_node(&mut self) {
+        if let Some(ref mut b) = self.cst {
+            b.finish_node();
+        }
+    }
+
+    pub fn checkpoint(&self) -> Option<Checkpoint> {
+        self.cst.as_ref().map(|b| b.checkpoint())
+    }
+
+    // Number of nodes open, to close the ones left open by an error.
+    pub fn node_depth(&self) -> usize {
+        self.cst.as_ref().map_or(0, |b| b.depth())
+    }
+
+    pub fn unwind_nodes(&mut self, depth : usize) {
+        if let Some(ref mut b) = self.cst {
+            b.unwind(depth);
+        }
+    }
+
+    // Span going from start to the end of the last token shifted.
+    pub fn span_from(&self, start : Span) -> Span {
+        start.to(self.prev_span)
+    }
 }
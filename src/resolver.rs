@@ -0,0 +1,292 @@
+/*
+    Name resolution: binds each use of an identifier to its declaration,
+    looking from the innermost block outward. Runs on the whole tree before
+    code generation and reports undeclared and duplicate identifiers,
+    breaks and continues that have no loop to jump to, and switches with the
+    same case twice. Functions are known before any body is visited, so they
+    can call each other in any order. Globals and constants are visible after
+    their declaration, in the functions and in the block.
+*/
+use std::collections::HashMap;
+use ast::statement::{Program, Item, Function, Block, Decl, DeclId, Type, Statement, StatementKind, Case,
+                     CaseLabel};
+use ast::expression::{NumExpr, NumExprKind, Loc, LocKind};
+use ast::visit::{MutVisitor, walk_block_mut, walk_statement_mut,
+                 walk_num_expr_mut, walk_loc_mut};
+use lexer::Span;
+use diagnostics::{Diagnostic, undeclared, redeclared, E_UNDECLARED, E_DUPLICATE_DECL, E_BREAK_OUTSIDE_LOOP, E_UNDECLARED_LABEL,
+                  E_DUPLICATE_CASE, E_RETURN_OUTSIDE_FN, E_ENTRY_POINT, E_ASSIGN_CONST};
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum DeclKind {
+    Local,
+    Param,      // Assigned by the call.
+    Global,     // In static storage, zero before the program starts.
+    Const,      // Assigned once, before the program starts.
+}
+
+// Declaration found in the program, indexed by its DeclId.
+#[derive(PartialEq, Debug, Clone)]
+pub struct DeclInfo {
+    pub name     : String,
+    pub type_id  : Type,
+    pub span     : Span,
+    pub kind     : DeclKind,
+}
+
+pub struct Resolver {
+    scopes          : Vec<HashMap<String, DeclId>>,   // One for each open block.
+    loops           : Vec<Option<String>>,            // Labels of the enclosing loops.
+    label           : Option<String>,                 // Label of the next loop.
+    functions       : HashMap<String, Span>,
+    in_function     : bool,
+    pub decls       : Vec<DeclInfo>,
+    pub diagnostics : Vec<Diagnostic>,
+}
+
+// Annotate the tree and return the declarations, or the errors found.
+pub fn resolve(program : &mut Program) -> Result<Vec<DeclInfo>, Vec<Diagnostic>> {
+    let mut r = Resolver::new();
+    r.visit_program(program);
+    if r.diagnostics.is_empty() {
+        Ok(r.decls)
+    } else {
+        Err(r.diagnostics)
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Resolver {
+            scopes : Vec::new(),
+            loops : Vec::new(),
+            label : None,
+            functions : HashMap::new(),
+            in_function : false,
+            decls : Vec::new(),
+            diagnostics : Vec::new(),
+        }
+    }
+
+    pub fn lookup(&self, name : &str) -> Option<DeclId> {
+        self.scopes.iter().rev()
+            .filter_map(|scope| scope.get(name))
+            .next()
+            .cloned()
+    }
+}
+
+impl Resolver {
+    // Check that a break or continue has a loop to go to.
+    fn jump(&mut self, keyword : &str, label : &Option<String>, span : Span) {
+        match *label {
+            Some(ref l) if !self.loops.contains(label) => {
+                let msg = format!("use of undeclared label `{}`", l);
+                self.diagnostics.push(Diagnostic::error(&msg, span)
+                    .with_code(E_UNDECLARED_LABEL)
+                    .with_primary_label("no enclosing loop has this label"));
+            },
+            None if self.loops.is_empty() => {
+                let msg = format!("`{}` outside of a loop", keyword);
+                self.diagnostics.push(Diagnostic::error(&msg, span)
+                    .with_code(E_BREAK_OUTSIDE_LOOP)
+                    .with_primary_label(&format!("cannot `{}` outside of a loop", keyword)));
+            },
+            _ => (),
+        }
+    }
+
+    fn call(&mut self, name : &str, span : Span) {
+        if !self.functions.contains_key(name) {
+            let msg = format!("cannot find function `{}`", name);
+            self.diagnostics.push(Diagnostic::error(&msg, span)
+                .with_code(E_UNDECLARED)
+                .with_primary_label("not found in this program"));
+        }
+    }
+
+    fn declare(&mut self, name : &str, type_id : Type, span : Span, kind : DeclKind) {
+        let prev = self.scopes.last().and_then(|scope| scope.get(name)).cloned();
+        if let Some(prev) = prev {
+            let diag = redeclared(name, span, self.decls[prev.0].span);
+            self.diagnostics.push(diag);
+            return;
+        }
+        let id = DeclId(self.decls.len());
+        self.decls.push(DeclInfo {
+            name    : String::from(name),
+            type_id,
+            span,
+            kind,
+        });
+        self.scopes.last_mut().unwrap().insert(String::from(name), id);
+    }
+
+    // The program starts from its block, or from a `void main()` without
+    // parameters.
+    fn entry_point(&mut self, p : &Program) {
+        let main = p.functions().into_iter().find(|f| f.name == "main");
+        let msg = match (main, &p.block) {
+            (None, &None) => Some(("`main` function not found", p.span, "add `void main()` or a program block")),
+            (Some(f), &Some(_)) => Some(("a program with a block cannot have a `main` function", f.span,
+                                         "the block is the entry point")),
+            (Some(f), &None) if !f.params.is_empty() || f.ret.is_some() => {
+                Some(("`main` must take no parameters and return no value", f.span, "not `void main()`"))
+            },
+            _ => None,
+        };
+        if let Some((msg, span, label)) = msg {
+            self.diagnostics.push(Diagnostic::error(msg, span)
+                .with_code(E_ENTRY_POINT)
+                .with_primary_label(label));
+        }
+    }
+
+    fn duplicate_cases(&mut self, cases : &[Case]) {
+        for (i, c) in cases.iter().enumerate() {
+            if let Some(prev) = cases[..i].iter().find(|p| p.label == c.label) {
+                let msg = match c.label {
+                    CaseLabel::Int(x) => format!("duplicate case label `{}`", x),
+                    CaseLabel::Bool(b) => format!("duplicate case label `{}`", if b { "True" } else { "False" }),
+                    CaseLabel::Default => String::from("multiple `default` cases"),
+                };
+                self.diagnostics.push(Diagnostic::error(&msg, c.span)
+                    .with_code(E_DUPLICATE_CASE)
+                    .with_primary_label("duplicate case")
+                    .with_label(prev.span, "first used here"));
+            }
+        }
+    }
+}
+
+impl MutVisitor for Resolver {
+    fn visit_program(&mut self, p : &mut Program) {
+        for f in p.functions() {
+            if let Some(&prev) = self.functions.get(&f.name) {
+                let msg = format!("function `{}` is defined more than once", f.name);
+                self.diagnostics.push(Diagnostic::error(&msg, f.span)
+                    .with_code(E_DUPLICATE_DECL)
+                    .with_primary_label("redefined here")
+                    .with_label(prev, "previous definition here"));
+            } else {
+                self.functions.insert(f.name.clone(), f.span);
+            }
+        }
+        self.entry_point(p);
+        self.scopes.push(HashMap::new());
+        for item in &mut p.items {
+            match *item {
+                Item::Global(ref d) => self.declare(&d.id, (*d.type_id).clone(), d.span, DeclKind::Global),
+                // The value cannot use the constant itself.
+                Item::Const(ref mut c) => {
+                    self.visit_bool_expr(&mut c.value);
+                    let t = Type { basic_type : c.basic_type, element_width : 4, dim_width : Vec::new() };
+                    self.declare(&c.name, t, c.span, DeclKind::Const);
+                },
+                Item::Function(ref mut f) => self.visit_function(f),
+            }
+        }
+        if let Some(ref mut b) = p.block {
+            self.visit_block(b);
+        }
+        self.scopes.pop();
+    }
+
+    // The parameters have a scope around the body.
+    fn visit_function(&mut self, f : &mut Function) {
+        self.in_function = true;
+        self.scopes.push(HashMap::new());
+        for d in &f.params {
+            self.declare(&d.id, (*d.type_id).clone(), d.span, DeclKind::Param);
+        }
+        self.visit_block(&mut f.body);
+        self.scopes.pop();
+        self.in_function = false;
+    }
+
+    // The declarations of a block are visible in all its statements.
+    fn visit_block(&mut self, b : &mut Block) {
+        self.scopes.push(HashMap::new());
+        walk_block_mut(self, b);
+        self.scopes.pop();
+    }
+
+    fn visit_decl(&mut self, d : &mut Decl) {
+        self.declare(&d.id, (*d.type_id).clone(), d.span, DeclKind::Local);
+    }
+
+    fn visit_statement(&mut self, s : &mut Statement) {
+        match s.kind {
+            StatementKind::While(..) | StatementKind::DoWhile(..) => {
+                let label = self.label.take();
+                self.loops.push(label);
+                walk_statement_mut(self, s);
+                self.loops.pop();
+            },
+            // The variable declared in the header has a scope of its own.
+            StatementKind::For(_) => {
+                let label = self.label.take();
+                self.loops.push(label);
+                self.scopes.push(HashMap::new());
+                walk_statement_mut(self, s);
+                self.scopes.pop();
+                self.loops.pop();
+            },
+            StatementKind::Labeled(ref name, ref mut body) => {
+                self.label = Some(name.clone());
+                self.visit_statement(body);
+            },
+            StatementKind::Switch(_, ref cases) => {
+                self.duplicate_cases(cases);
+                walk_statement_mut(self, s);
+            },
+            StatementKind::Break(ref label) => self.jump("break", label, s.span),
+            StatementKind::Continue(ref label) => self.jump("continue", label, s.span),
+            StatementKind::Return(_) if !self.in_function => {
+                self.diagnostics.push(Diagnostic::error("`return` outside of a function", s.span)
+                    .with_code(E_RETURN_OUTSIDE_FN)
+                    .with_primary_label("the program block cannot return"));
+                walk_statement_mut(self, s);
+            },
+            StatementKind::Call(ref name, _) => {
+                self.call(name, s.span);
+                walk_statement_mut(self, s);
+            },
+            StatementKind::Assign(..) => {
+                walk_statement_mut(self, s);
+                if let StatementKind::Assign(ref l, _) = s.kind {
+                    let decl = l.decl.map(|id| &self.decls[id.0]);
+                    if let Some(d) = decl.filter(|d| d.kind == DeclKind::Const) {
+                        let msg = format!("cannot assign to constant `{}`", d.name);
+                        self.diagnostics.push(Diagnostic::error(&msg, l.span)
+                            .with_code(E_ASSIGN_CONST)
+                            .with_primary_label("cannot assign")
+                            .with_label(d.span, "constant declared here"));
+                    }
+                }
+            },
+            _ => walk_statement_mut(self, s),
+        }
+    }
+
+    fn visit_num_expr(&mut self, e : &mut NumExpr) {
+        if let NumExprKind::Call(ref name, _) = e.kind {
+            self.call(name, e.span);
+        }
+        walk_num_expr_mut(self, e);
+    }
+
+    fn visit_loc(&mut self, l : &mut Loc) {
+        let decl = match l.kind {
+            LocKind::Ide(ref s) | LocKind::Index(ref s, _) => {
+                let decl = self.lookup(s);
+                if decl.is_none() {
+                    self.diagnostics.push(undeclared(s, l.span));
+                }
+                decl
+            },
+        };
+        l.decl = decl;
+        walk_loc_mut(self, l);
+    }
+}
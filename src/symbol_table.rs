@@ -1,11 +1,14 @@
 use std::collections::HashMap;
 use ast::statement::Type;
 use code_generator::Address;
+use lexer::Span;
+use diagnostics::{Diagnostic, redeclared};
 use std::iter::Iterator;
 
 pub struct IdeInfo {
     pub typeinfo : Type,   
     pub address  : Address,
+    pub span     : Span,    // Location of the declaration.
 }
 
 struct Frame {
@@ -13,14 +16,17 @@ struct Frame {
     table :HashMap<String, IdeInfo>,
 }
 
+// Globals have static storage, outside of the frames.
 pub struct SymbolTable {
     frame_stack : Vec<Frame>,
+    globals     : HashMap<String, IdeInfo>,
 }
 
 impl SymbolTable {
     pub fn new() -> SymbolTable {
         SymbolTable {
             frame_stack : Vec::new(),
+            globals     : HashMap::new(),
         }    
     } 
     pub fn push_frame(&mut self) {
@@ -35,24 +41,57 @@ impl SymbolTable {
         self.frame_stack.pop();
     }
     
-    pub fn put(&mut self, name : String, typeinfo : Type, address : Address) {
+    pub fn put(&mut self, name : String, typeinfo : Type, address : Address, span : Span)
+            -> Result<(), Box<Diagnostic>> {
+        let n = self.frame_stack.len();
+        if let Some(prev) = self.frame_stack[n-1].table.get(&name) {
+            return Err(Box::new(redeclared(&name, span, prev.span)));
+        }
         let info = IdeInfo {
             typeinfo : typeinfo,
             address  : address,
+            span,
         };
-        let n = self.frame_stack.len();
         let array_width = info.typeinfo.dim_width.iter().fold(0, |sum, x| sum + x);
         let ide_width = info.typeinfo.element_width * array_width;
         self.frame_stack[n-1].table.insert(name, info);        
         self.frame_stack[n-1].width += ide_width;
+        Ok(())
     }
-    // BUG: should search other frame if not found.
+    pub fn put_global(&mut self, name : String, typeinfo : Type, address : Address, span : Span)
+            -> Result<(), Box<Diagnostic>> {
+        if let Some(prev) = self.globals.get(&name) {
+            return Err(Box::new(redeclared(&name, span, prev.span)));
+        }
+        self.globals.insert(name, IdeInfo {
+            typeinfo,
+            address,
+            span,
+        });
+        Ok(())
+    }
+
+    // Search from the innermost frame outward, then the globals.
     pub fn get_ide(&self, name : &str) -> Option<&IdeInfo> {
-        self.frame_stack[self.frame_stack.len()-1]
-            .table.get(name)
+        self.frame_stack.iter().rev()
+            .filter_map(|f| f.table.get(name))
+            .next()
+            .or_else(|| self.globals.get(name))
     }
     
+    // Number of frames open.
+    pub fn depth(&self) -> usize {
+        self.frame_stack.len()
+    }
+
+    pub fn frame_width(&self, i : usize) -> u32 {
+        self.frame_stack[i].width
+    }
+
     pub fn get_frame_width(&self) -> u32 {
-        self.frame_stack[self.frame_stack.len() - 1].width
+        match self.frame_stack.last() {
+            Some(f) => f.width,
+            None => 0,
+        }
     }
 }
@@ -0,0 +1,86 @@
+#[cfg(test)]
+mod test {
+    use cfg::{Cfg, BasicBlock, Edge, EdgeKind};
+    use code_generator::{CodeGenerator, IntermediateRepresentation};
+    use lexer::Scanner;
+    use parser::Parser;
+
+    fn compile(s : &str) -> IntermediateRepresentation {
+        let mut code_gen = CodeGenerator::new(Parser::new(Scanner::from_str(s)));
+        code_gen.generate_code().unwrap();
+        code_gen.code
+    }
+
+    #[test]
+    fn listing_test() {
+        let code = compile("{ float x; x = -(1 + 2.5); }");
+        let lines : Vec<String> = code.instructions.iter().map(|i| i.to_string()).collect();
+        assert_eq!(lines, vec![
+            "1: t1 = add t1, 0",
+            "2: t4 = inttofloat 1",
+            "3: t3 = fadd t4, f0",
+            "4: t5 = fminus t3",
+            "5: t2 = t5",
+            "6: t1 = sub t1, 0",
+        ]);
+    }
+
+    #[test]
+    fn blocks_test() {
+        let code = compile("{ int x; while (x < 3) if (x == 1) x = 2; else x = x + 1; }");
+        let cfg = Cfg::new(&code);
+        let blocks : Vec<(usize, usize)> = cfg.blocks.iter().map(|b| (b.start, b.end)).collect();
+        assert_eq!(blocks, vec![(0, 1), (1, 4), (4, 7), (7, 9), (9, 11), (11, 12), (12, 13)]);
+        let edge = |from, to, kind| Edge { from, to, kind };
+        assert_eq!(cfg.edges, vec![
+            edge(0, 1, EdgeKind::Fallthrough),
+            edge(1, 6, EdgeKind::IfZero),
+            edge(1, 2, EdgeKind::IfNotZero),
+            edge(2, 4, EdgeKind::IfZero),
+            edge(2, 3, EdgeKind::IfNotZero),
+            edge(3, 5, EdgeKind::Goto),
+            edge(4, 5, EdgeKind::Fallthrough),
+            edge(5, 1, EdgeKind::Goto),
+            edge(6, 7, EdgeKind::Fallthrough),
+        ]);
+        assert_eq!(cfg.exit(), 7);
+        assert_eq!(cfg.blocks[6], BasicBlock { start : 12, end : 13 });
+    }
+
+    #[test]
+    fn dot_test() {
+        let code = compile("{ bool x; if (x) x = True; }");
+        let expected = "\
+digraph cfg {
+    node [shape=box, fontname=\"monospace\"];
+    entry [shape=oval];
+    exit [shape=oval];
+    b0 [label=\"1: t1 = add t1, 0\\l2: jmpz t2, L4\\l\"];
+    b1 [label=\"3: t2 = 1\\l\"];
+    b2 [label=\"4: t1 = sub t1, 0\\l\"];
+    entry -> b0;
+    b0 -> b2 [label=\"zero\"];
+    b0 -> b1 [label=\"not zero\"];
+    b1 -> b2;
+    b2 -> exit;
+}
+";
+        assert_eq!(Cfg::new(&code).to_dot(&code), expected);
+    }
+
+    #[test]
+    fn jump_table_test() {
+        // Dense cases jump through a table, with an entry for each value.
+        let code = compile("{ int x; switch (x) { case 1: x = 0; case 2: x = 1; case 4: x = 2; } }");
+        let cfg = Cfg::new(&code);
+        let table : Vec<_> = cfg.edges.iter().filter_map(|e| match e.kind {
+            EdgeKind::Table(k) => Some(k),
+            _ => None,
+        }).collect();
+        assert_eq!(table, vec![0, 1, 2, 3]);
+        // Sparse ones compare the cases in order.
+        let code = compile("{ int x; switch (x) { case 1: x = 0; case 20: x = 1; case 400: x = 2; } }");
+        assert!(Cfg::new(&code).edges.iter().all(|e| e.kind != EdgeKind::Table(0)));
+        assert_eq!(Cfg::new(&code).edges.iter().filter(|e| e.kind == EdgeKind::IfZero).count(), 3);
+    }
+}
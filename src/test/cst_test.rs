@@ -0,0 +1,124 @@
+#[cfg(test)]
+mod test {
+    use cst;
+    use cst::{NodeKind, TriviaKind, SyntaxNode, SyntaxElement};
+    use lexer;
+    use parser;
+
+    fn parse(s : &str) -> SyntaxNode {
+        let (tree, errors) = cst::parse(lexer::Scanner::from_str(s));
+        assert!(errors.is_empty(), "{:?}", errors);
+        tree
+    }
+
+    // Direct children that are nodes.
+    fn nodes(n : &SyntaxNode) -> Vec<&SyntaxNode> {
+        n.children.iter().filter_map(|c| match *c {
+            SyntaxElement::Node(ref n) => Some(n),
+            SyntaxElement::Token(_) => None,
+        }).collect()
+    }
+
+    fn round_trip(s : &str) {
+        let (tree, _) = cst::parse(lexer::Scanner::from_str(s));
+        assert_eq!(tree.to_string(), s);
+    }
+
+    #[test]
+    fn round_trip_test() {
+        round_trip("{}");
+        round_trip("");
+        round_trip("  \n{ int x;\tx = (1 + 2) * 3 ;\n}\n\n");
+        round_trip("// header\n{ /* a /* nested */ comment */ float[2][3] a;\r\n\
+                    while (a[0][1] >= -.5e1 || !True) { break; } } // end");
+        round_trip("{ if (x == 1 && y != 2) x = 1; else { y = x < 2; } }");
+        round_trip("{ switch(x){ case - 1 : x = 2; default:} }");
+        round_trip("{ l: for ( int i=0 ;; i = i+1) do { continue l; } while (i < 3) ; }");
+        round_trip("int f ( int n , float x ) { return f(n, x) ; } void p() { p ( ) ; return ; } { }");
+        round_trip("const int N=1; int [2] g ;void main(){g[0] = N;}");
+        // Lexical errors, syntax errors and text after the program.
+        round_trip("{ int x; x = 1 @ 2; }");
+        round_trip("{ x = 1 + ; y = 2; int ; }");
+        round_trip("{ while (x) x = 1 } ) junk");
+        round_trip("{ x = 1; /* never closed");
+        round_trip("{ x = 1 | 2;");
+    }
+
+    #[test]
+    fn tree_test() {
+        let tree = parse("{ int x; x = -(1 + 2) * 3; }");
+        let expected = "\
+File
+  Program
+    Block
+      OpenBlock \"{\"
+      Decl
+        Type
+          Type \"int\"
+        Ide \"x\"
+        SemiColon \";\"
+      AssignStmt
+        Loc
+          Ide \"x\"
+        Assign \"=\"
+        BinaryExpr
+          UnaryExpr
+            NumOp \"-\"
+            ParenExpr
+              LParen \"(\"
+              BinaryExpr
+                Literal
+                  Num \"1\"
+                NumOp \"+\"
+                Literal
+                  Num \"2\"
+              RParen \")\"
+          NumOp \"*\"
+          Literal
+            Num \"3\"
+        SemiColon \";\"
+      CloseBlock \"}\"
+  Eof \"\"
+";
+        assert_eq!(tree.dump(), expected);
+    }
+
+    #[test]
+    fn trivia_test() {
+        let tree = parse("{ x = 1; // one\n  @ y = 2; }");
+        let tokens = tree.tokens();
+        let y = tokens.iter().find(|t| t.text == "y").unwrap();
+        let kinds : Vec<TriviaKind> = y.leading.iter().map(|t| t.kind).collect();
+        assert_eq!(kinds, vec![TriviaKind::Whitespace, TriviaKind::Comment,
+                               TriviaKind::Whitespace, TriviaKind::Skipped,
+                               TriviaKind::Whitespace]);
+        assert_eq!(y.leading[1].text, "// one");
+        assert_eq!(y.leading[3].text, "@");
+    }
+
+    #[test]
+    fn error_test() {
+        let (tree, errors) = cst::parse(lexer::Scanner::from_str("{ x = + 1; y = 2; }"));
+        assert_eq!(errors.len(), 1);
+        let block = nodes(nodes(&tree)[0])[0];
+        let kinds : Vec<NodeKind> = nodes(block).iter().map(|n| n.kind).collect();
+        assert_eq!(kinds, vec![NodeKind::Error, NodeKind::AssignStmt]);
+    }
+
+    #[test]
+    fn to_ast_test() {
+        let s = "{ int x; float[2] a; /* c */ x = 1; \
+                 while (x < 10 && !False) { if (x == 3) break; else x = x + 1; } \
+                 a[1] = x * .5; }";
+        // The abstract syntax tree is built along with the concrete one.
+        let mut parser = parser::Parser::with_cst(lexer::Scanner::from_str(s));
+        parser.parse().unwrap();
+        let tree = parser.take_cst().unwrap();
+        assert_eq!(tree.to_string(), s);
+        let ast = parser.get_root();
+        let mut parser = parser::Parser::new(lexer::Scanner::from_str(s));
+        parser.parse().unwrap();
+        assert_eq!(ast, parser.get_root());
+        assert_eq!(ast.span.hi, s.len());
+    }
+}
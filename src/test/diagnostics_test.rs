@@ -0,0 +1,102 @@
+#[cfg(test)]
+mod test {
+    use diagnostics::{Diagnostic, SourceFile};
+    use code_generator::CodeGenerator;
+    use lexer;
+    use parser;
+    
+    fn compile(s : &str) -> Vec<Diagnostic> {
+        let scanner = lexer::Scanner::new_static(String::from(s));
+        let parser = parser::Parser::new(scanner);
+        let mut code_gen = CodeGenerator::new(parser);
+        match code_gen.generate_code() {
+            Ok(()) => Vec::new(),
+            Err(diags) => diags,
+        }
+    }
+    
+    fn render(s : &str) -> String {
+        let file = SourceFile::new("test.src", s);
+        compile(s).iter().map(|d| d.render(&file)).collect::<Vec<_>>().join("\n")
+    }
+    
+    #[test]
+    fn syntax_error_test() {
+        let s = "{\n    int x;\n    x = 3 +;\n}";
+        let expected = "\
+error[E0001]: expected expression, found `;`
+ --> test.src:3:12
+  |
+3 |     x = 3 +;
+  |            ^ expected expression
+";
+        assert_eq!(render(s), expected);
+    }
+    
+    #[test]
+    fn undeclared_test() {
+        let s = "{ int x;\n\tx = yy; }";
+        let expected = "\
+error[E0101]: undeclared identifier `yy`
+ --> test.src:2:6
+  |
+2 | \tx = yy; }
+  | \t    ^^ not found in this scope
+";
+        assert_eq!(render(s), expected);
+    }
+    
+    #[test]
+    fn labels_and_notes_test() {
+        let s = "{\n  int x;\n  float y;\n  int x;\n}";
+        let diags = compile(s);
+        assert_eq!(diags.len(), 1);
+        let d = diags[0].clone().with_note("each name can be declared once per block");
+        let expected = "\
+error[E0102]: `x` is already declared in this block
+ --> test.src:4:3
+  |
+2 |   int x;
+  |   ------ previous declaration here
+  |
+4 |   int x;
+  |   ^^^^^^ redeclared here
+  = note: each name can be declared once per block
+";
+        assert_eq!(d.render(&SourceFile::new("test.src", s)), expected);
+        assert_eq!(format!("{}", d), "4:3: error[E0102]: `x` is already declared in this block");
+    }
+    
+    #[test]
+    fn lexical_error_test() {
+        let s = "{ int x; x = 3 $ 4; }";
+        let expected = "\
+error[E0002]: unknown character `$`
+ --> test.src:1:16
+  |
+1 | { int x; x = 3 $ 4; }
+  |                ^ not a valid token
+
+error[E0001]: expected `;`, found `4`
+ --> test.src:1:18
+  |
+1 | { int x; x = 3 $ 4; }
+  |                  ^ expected `;`
+";
+        assert_eq!(render(s), expected);
+    }
+    
+    #[test]
+    fn type_error_test() {
+        let s = "{ int x; float y;\n  x = y * 2; }";
+        let expected = "\
+error[E0201]: mismatched types: expected `int`, found `float`
+ --> test.src:2:7
+  |
+2 |   x = y * 2; }
+  |       ^^^^^ expected `int`
+  |   - has type `int`
+";
+        assert_eq!(render(s), expected);
+    }
+}
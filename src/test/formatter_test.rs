@@ -0,0 +1,123 @@
+#[cfg(test)]
+mod test {
+    use ast::expression::{BoolExpr as B, NumExpr as N, Loc};
+    use ast::expression::{BoolExprKind as BK, NumExprKind as NK, LocKind};
+    use ast::statement::{Program, Block, Statement as S, StatementKind as SK};
+    use formatter::{format_source, format_program};
+    use lexer::{Scanner, Span};
+    use parser::Parser;
+
+    fn format(s : &str) -> String {
+        format_source(Scanner::from_str(s)).unwrap()
+    }
+
+    fn parse(s : &str) -> Box<Program> {
+        Parser::new(Scanner::from_str(s)).parse_program().unwrap()
+    }
+
+    fn ide(s : &str) -> Box<N> {
+        N::new(NK::Loc(Loc::new(LocKind::Ide(String::from(s)), Span::default())), Span::default())
+    }
+
+    fn num(k : NK) -> Box<N> {
+        N::new(k, Span::default())
+    }
+
+    fn boolean(k : BK) -> Box<B> {
+        B::new(k, Span::default())
+    }
+
+    fn program(stmts : Vec<Box<S>>) -> Program {
+        let block = Block { decls : vec![], stmts, span : Span::default() };
+        Program { items : Vec::new(), block : Some(Box::new(block)), span : Span::default() }
+    }
+
+    fn assign(e : Box<B>) -> Box<S> {
+        let l = Loc::new(LocKind::Ide(String::from("x")), Span::default());
+        S::new(SK::Assign(l, e), Span::default())
+    }
+
+    #[test]
+    fn layout_test() {
+        let s = "// Header.\n{ int x;float[2][3]  a ;x=1+2*3; // one\n\n\n\
+                 while(x<10&&!(x==3)){x=x-1;if(x>=2)break;else if (x<0) x=-x; else {}}\n\
+                 a[1][x] = .5e1 ; }";
+        let expected = "\
+// Header.
+{
+    int x;
+    float[2][3] a;
+    x = 1 + 2 * 3; // one
+
+    while (x < 10 && !(x == 3)) {
+        x = x - 1;
+        if (x >= 2)
+            break;
+        else if (x < 0)
+            x = -x;
+        else {}
+    }
+    a[1][x] = 5.0;
+}
+";
+        assert_eq!(format(s), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn reparse_test() {
+        let sources = [
+            "{ x = ((1)) - (2 - 3) - 4 / (5 * 6); }",
+            "{ x = a || b && c == d != e < f + -g * !h; }",
+            "{ x = (a || b) && (c || d); y = -(-x); }",
+            "{ int x; { float y; /* inner */ { } } x = 1; int z; }",
+            "{ if (a) if (b) x = 1; else x = 2; while (True) { break; } }",
+            "{ if (a) { x = 1; } else if (b) x = 2; else while (c) x = 3; }",
+            "{ l: while (a) { while (b) { if (c) continue l; else break l; continue; } } }",
+            "{ for (int i = 0; i < n; i = i + 1) for (;;) break; do x = 1; while (x); }",
+            "{ l: for (x = 0; x < 3;) { do { continue l; } while (False); } }",
+            "{ switch (x + 1) { case -1: case 2: x = 1; y = 2; default: { } } switch (b) { } }",
+            "int f(int n, float x) { return n * f(n - 1, x); } void p() { return; } { p(); x = f(1, 2.0) + g(); }",
+            "int g; const float PI = 3.14; float[2] a; void main() { g = g + 1; } const bool B = !False;",
+        ];
+        for s in sources.iter() {
+            let formatted = format(s);
+            assert_eq!(parse(&formatted), parse(s), "{}", formatted);
+            assert_eq!(format(&formatted), formatted);
+        }
+    }
+
+    #[test]
+    fn precedence_test() {
+        // Trees built by hand get parentheses where the parser needs them.
+        let e = num(NK::Mul(num(NK::Add(ide("a"), ide("b"))), ide("c")));
+        let e1 = boolean(BK::NumExpr(e));
+        let e = num(NK::Sub(ide("a"), num(NK::Sub(ide("b"), ide("c")))));
+        let e2 = boolean(BK::NumExpr(num(NK::Minus(e))));
+        let or = boolean(BK::Or(boolean(BK::NumExpr(ide("a"))), boolean(BK::NumExpr(ide("b")))));
+        let e3 = boolean(BK::And(or, boolean(BK::NumExpr(ide("c")))));
+        let p = program(vec![assign(e1), assign(e2), assign(e3)]);
+        let expected = "{\n    x = (a + b) * c;\n    x = -(a - (b - c));\n    x = (a || b) && c;\n}\n";
+        assert_eq!(format_program(&p, "", Vec::new()), expected);
+    }
+
+    #[test]
+    fn dangling_else_test() {
+        let c = || boolean(BK::NumExpr(ide("c")));
+        let one = || assign(boolean(BK::NumExpr(num(NK::Num(1)))));
+        let inner = S::new(SK::If(c(), one()), Span::default());
+        let p = program(vec![S::new(SK::IfElse(c(), inner, one()), Span::default())]);
+        let formatted = format_program(&p, "", Vec::new());
+        assert_eq!(formatted, "{\n    if (c) {\n        if (c)\n            x = 1;\n    } else\n        x = 1;\n}\n");
+        match parse(&formatted).block.unwrap().stmts[0].kind {
+            SK::IfElse(..) => (),
+            ref k => panic!("else attached to the wrong if: {:?}", k),
+        }
+    }
+
+    #[test]
+    fn error_test() {
+        let errors = format_source(Scanner::from_str("{ x = ; @ }")).unwrap_err();
+        assert_eq!(errors.len(), 2);
+    }
+}
@@ -0,0 +1,246 @@
+#[cfg(test)]
+mod test {
+    use code_generator::CodeGenerator;
+    use interpreter::{Interpreter, Value, RuntimeError};
+    use lexer;
+    use parser;
+    
+    fn run(s : &str) -> Interpreter {
+        let mut inter = compile(s);
+        inter.execute().unwrap();
+        inter
+    }
+    
+    // Value of the last variable declared with the given name.
+    fn get_variable(inter : &Interpreter, name : &str) -> Option<Value> {
+        inter.variables().into_iter().rev()
+            .find(|&(n, _)| n == name)
+            .map(|(_, v)| v)
+    }
+    
+    fn compile(s : &str) -> Interpreter {
+        let scanner = lexer::Scanner::new_static(String::from(s));
+        let parser = parser::Parser::new(scanner);
+        let mut code_gen = CodeGenerator::new(parser);
+        if let Err(diags) = code_gen.generate_code() {
+            panic!("Compilation failed: {}", diags[0]);
+        }
+        Interpreter::new(code_gen.code)
+    }
+    
+    #[test]
+    fn int_test() {
+        let inter = run("{int x; int y; x = 3 * (4 + 2) - 8 / 2; y = -x;}");
+        assert_eq!(get_variable(&inter, "x"), Some(Value::Int(14)));
+        assert_eq!(get_variable(&inter, "y"), Some(Value::Int(-14)));
+    }
+    
+    #[test]
+    fn overflow_test() {
+        // Int arithmetic wraps around.
+        let inter = run("{int x; int y; int z; x = 2147483647; x = x + 1; y = -x; z = x / -1;}");
+        assert_eq!(get_variable(&inter, "x"), Some(Value::Int(-2147483648)));
+        assert_eq!(get_variable(&inter, "y"), Some(Value::Int(-2147483648)));
+        assert_eq!(get_variable(&inter, "z"), Some(Value::Int(-2147483648)));
+        let inter = run("{int x; x = 65536; x = x * x - 1;}");
+        assert_eq!(get_variable(&inter, "x"), Some(Value::Int(-1)));
+        
+        let mut inter = compile("{int x; int y; y = 0; x = 1 / y;}");
+        assert_eq!(inter.execute(), Err(RuntimeError::DivisionByZero));
+    }
+    
+    #[test]
+    fn float_test() {
+        let inter = run("{float x; x = 1.5 * 2.0;}");
+        assert_eq!(get_variable(&inter, "x"), Some(Value::Float(3.0)));
+        
+        // Int operands and values are promoted to float.
+        let inter = run("{float x; float y; x = 1 / 4.0 + .5e1; y = x * 2;}");
+        assert_eq!(get_variable(&inter, "x"), Some(Value::Float(5.25)));
+        assert_eq!(get_variable(&inter, "y"), Some(Value::Float(10.5)));
+        
+        let inter = run("{int a; int b; if (0.5 < 1) a = 1; if (2.0 == 2) b = 1;}");
+        assert_eq!(get_variable(&inter, "a"), Some(Value::Int(1)));
+        assert_eq!(get_variable(&inter, "b"), Some(Value::Int(1)));
+    }
+    
+    #[test]
+    fn control_flow_test() {
+        let s = "{int i; int s; int c; i = 0; s = 0; c = 0;
+                  while (i < 5) i = i + 1;
+                  if (i == 2 || i != i) c = c + 10; else c = c + 1;
+                  if (i >= 5 && !(i > 5)) s = 10;
+                 }";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "s"), Some(Value::Int(10)));
+        assert_eq!(get_variable(&inter, "c"), Some(Value::Int(1)));
+        assert_eq!(get_variable(&inter, "i"), Some(Value::Int(5)));
+    }
+    
+    #[test]
+    fn nested_scope_test() {
+        // Inner blocks see the variables of the outer ones.
+        let s = "{int x; int y; x = 1; { int z; z = x + 1; { y = z * 10; } } { float x; x = 2.5; } }";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "y"), Some(Value::Int(20)));
+        assert_eq!(get_variable(&inter, "z"), Some(Value::Int(2)));
+    }
+    
+    #[test]
+    fn index_test() {
+        // Computing the offset leaves the index variable alone.
+        let inter = run("{int[3] a; int[2][3] b; int i; int j; int s; i = 1; j = 2; a[i] = 5; b[i][j] = 6; s = i + j;}");
+        assert_eq!(get_variable(&inter, "i"), Some(Value::Int(1)));
+        assert_eq!(get_variable(&inter, "j"), Some(Value::Int(2)));
+        assert_eq!(get_variable(&inter, "s"), Some(Value::Int(3)));
+    }
+    
+    #[test]
+    fn bool_test() {
+        let s = "{bool done; bool b; int i; done = False;
+                  while (!done) { i = i + 1; done = i >= 3; }
+                  b = done && i == 3 && b != done;
+                 }";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "i"), Some(Value::Int(3)));
+        assert_eq!(get_variable(&inter, "done"), Some(Value::Bool(true)));
+        assert_eq!(get_variable(&inter, "b"), Some(Value::Bool(true)));
+        assert_eq!(format!("{} {}", Value::Bool(true), Value::Bool(false)), "true false");
+    }
+    
+    #[test]
+    fn break_test() {
+        // Break leaves the innermost loop, from inside blocks and ifs.
+        let s = "{int i; int j; int n; i = 0; n = 0;
+                  while (i < 10) {
+                      i = i + 1;
+                      j = 0;
+                      while (True) { j = j + 1; { if (j >= i) break; } n = n + 1; }
+                      if (i == 4) { { break; } }
+                  }
+                 }";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "i"), Some(Value::Int(4)));
+        assert_eq!(get_variable(&inter, "j"), Some(Value::Int(4)));
+        assert_eq!(get_variable(&inter, "n"), Some(Value::Int(6)));
+    }
+
+    #[test]
+    fn continue_test() {
+        // Continue goes back to the condition, closing the blocks it leaves.
+        let s = "{int i; int n; i = 0; n = 0;
+                  while (i < 10) { i = i + 1; { int k; k = i; if (k == 3 || k == 7) continue; } n = n + i; }
+                 }";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "i"), Some(Value::Int(10)));
+        assert_eq!(get_variable(&inter, "n"), Some(Value::Int(45)));
+    }
+
+    #[test]
+    fn for_test() {
+        // Continue goes to the step, and the loop variable to its own frame.
+        let s = "{int s; int n; s = 0; n = 0;
+                  for (int i = 0; i < 10; i = i + 1) { if (i == 3) continue; if (i == 8) break; s = s + i; }
+                  for (n = 1; ; n = n * 2) { int t; t = n; if (t > 100) break; }
+                 }";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "s"), Some(Value::Int(25)));
+        assert_eq!(get_variable(&inter, "n"), Some(Value::Int(128)));
+    }
+
+    #[test]
+    fn do_while_test() {
+        // The body runs once even when the condition is false.
+        let s = "{int k; int n; k = 0; n = 0;
+                  do { k = k + 1; if (k == 2) continue; n = n + k; } while (k < 5);
+                  do k = k + 1; while (False);
+                 }";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "k"), Some(Value::Int(6)));
+        assert_eq!(get_variable(&inter, "n"), Some(Value::Int(13)));
+    }
+
+    #[test]
+    fn switch_test() {
+        // Dense cases, sparse cases and a switch on a bool, without fallthrough.
+        let s = "{int i; int d; int s; int b; d = 0; s = 0; b = 0;
+                  for (i = -1; i < 7; i = i + 1) {
+                      switch (i) {
+                          case 0: d = d + 1;
+                          case 1: d = d + 10;
+                          case 2: d = d + 100; if (i == 2) continue;
+                          case 4: d = d + 1000;
+                          default: d = d + 10000;
+                      }
+                      switch (i * 100) { case 100: s = s + 1; case -100: s = s + 2; case 500: s = s + 3; }
+                      switch (i > 3) { case True: b = b + 1; case False: b = b + 10; }
+                  }
+                 }";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "d"), Some(Value::Int(41111)));
+        assert_eq!(get_variable(&inter, "s"), Some(Value::Int(6)));
+        assert_eq!(get_variable(&inter, "b"), Some(Value::Int(43)));
+    }
+
+    #[test]
+    fn recursion_test() {
+        let s = "int fact(int n) { if (n <= 1) return 1; return n * fact(n - 1); }
+                 int fib(int n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+                 {int f; int g; f = fact(10); g = fib(15);}";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "f"), Some(Value::Int(3628800)));
+        assert_eq!(get_variable(&inter, "g"), Some(Value::Int(610)));
+    }
+
+    #[test]
+    fn call_test() {
+        // Arguments are converted to the parameter types, the registers of
+        // the caller are kept across calls.
+        let s = "float half(float x) { return x / 2; }
+                 bool odd(int n) { while (n > 1) n = n - 2; return n == 1; }
+                 void nothing(int a) { a = a + 1; }
+                 {int i; float h; bool b; i = 7; h = half(i); nothing(i); b = odd(i);}";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "h"), Some(Value::Float(3.5)));
+        assert_eq!(get_variable(&inter, "i"), Some(Value::Int(7)));
+        assert_eq!(get_variable(&inter, "b"), Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn globals_test() {
+        // Globals are shared by all the activations, constants are set before main.
+        let s = "const int N = 10; const float HALF = 1 / 2.0; int calls; float total;
+                 int fib(int n) { calls = calls + 1; if (n < 2) return n; return fib(n - 1) + fib(n - 2); }
+                 void main() { int i; for (i = 0; i <= N; i = i + 1) total = total + fib(i) * HALF; }";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "N"), Some(Value::Int(10)));
+        assert_eq!(get_variable(&inter, "calls"), Some(Value::Int(453)));
+        assert_eq!(get_variable(&inter, "total"), Some(Value::Float(71.5)));
+        assert_eq!(get_variable(&inter, "i"), None);
+
+        let inter = run("int g; void set(int x) { g = x; } {int y; set(4); y = g; g = g + 1;}");
+        assert_eq!(get_variable(&inter, "y"), Some(Value::Int(4)));
+        assert_eq!(get_variable(&inter, "g"), Some(Value::Int(5)));
+    }
+
+    #[test]
+    fn labeled_test() {
+        let s = "{int i; int j; int s; i = 0; s = 0;
+                  outer: while (i < 5) {
+                      i = i + 1;
+                      j = 0;
+                      while (j < 5) {
+                          j = j + 1;
+                          if (j == 2) continue;
+                          if (j == 4) continue outer;
+                          if (i == 4) break outer;
+                          s = s + j;
+                      }
+                  }
+                 }";
+        let inter = run(s);
+        assert_eq!(get_variable(&inter, "i"), Some(Value::Int(4)));
+        assert_eq!(get_variable(&inter, "j"), Some(Value::Int(1)));
+        assert_eq!(get_variable(&inter, "s"), Some(Value::Int(12)));
+    }
+}
@@ -5,18 +5,7 @@ use lexer::{Scanner};
 
 fn scan_string(s : String) -> String {
     let mut scanner = lexer::Scanner::new_static(s);
-    let mut v = vec![];
-    let mut tok = scanner.scan();
-    while tok.tag != lexer::Tag::Eof {
-        v.push(tok);
-        tok = scanner.scan();
-    }
-
-    let mut s = String::new();
-    for el in v {
-        s.push_str(&el.to_cow_string());
-    }
-    s
+    (&mut scanner).map(|tok| tok.to_string()).collect()
 }
 
 #[test]
@@ -33,4 +22,145 @@ fn basic_test() {
     let res = scan_string(String::from(s));
     assert_eq!(res, String::from(s));
 }
+
+#[test]
+fn span_test() {
+    let mut scanner = lexer::Scanner::new_static(String::from("x = 10;\n  while"));
+    let mut v = vec![];
+    let mut tok = scanner.scan();
+    while tok.tag != lexer::Tag::Eof {
+        let sp = tok.span;
+        v.push((sp.lo, sp.hi, sp.line, sp.col));
+        tok = scanner.scan();
+    }
+    assert_eq!(v, vec![(0, 1, 1, 1), (2, 3, 1, 3), (4, 6, 1, 5), (6, 7, 1, 7), (10, 15, 2, 3)]);
+    assert_eq!((tok.span.lo, tok.span.hi), (15, 15));
+}
+
+#[test]
+fn error_test() {
+    let mut scanner = lexer::Scanner::new_static(String::from("x = 3 $ 4 | y;"));
+    let mut v = vec![];
+    let mut tok = scanner.scan();
+    while tok.tag != lexer::Tag::Eof {
+        v.push(tok);
+        tok = scanner.scan();
+    }
+    let tags : Vec<_> = v.iter().map(|t| t.tag).collect();
+    assert_eq!(tags, vec![lexer::Tag::Ide, lexer::Tag::Assign, lexer::Tag::Num,
+                          lexer::Tag::Error, lexer::Tag::Num, lexer::Tag::Error,
+                          lexer::Tag::Ide, lexer::Tag::SemiColon]);
+    assert_eq!(v[3].info, lexer::TokenInfo::Error(lexer::LexError::UnknownChar('$')));
+    assert_eq!(v[5].info, lexer::TokenInfo::Error(lexer::LexError::LoneChar('|')));
+    assert_eq!((v[5].span.lo, v[5].span.hi), (10, 11));
+    
+    let msgs : Vec<_> = scanner.errors.iter().map(|d| format!("{}", d)).collect();
+    assert_eq!(msgs, vec!["1:7: error[E0002]: unknown character `$`",
+                          "1:11: error[E0002]: unknown operator `|`"]);
+}
+
+#[test]
+fn real_test() {
+    let s = "3.25 1e-3 .5 2.5E+2 10";
+    let mut scanner = lexer::Scanner::new_static(String::from(s));
+    let mut v = vec![];
+    let mut tok = scanner.scan();
+    while tok.tag != lexer::Tag::Eof {
+        v.push(tok.info);
+        tok = scanner.scan();
+    }
+    assert_eq!(v, vec![lexer::TokenInfo::Real(3.25), lexer::TokenInfo::Real(0.001),
+                       lexer::TokenInfo::Real(0.5), lexer::TokenInfo::Real(250.0),
+                       lexer::TokenInfo::Num(10)]);
+    
+    let res = scan_string(String::from("x=1.5*2.0;"));
+    assert_eq!(res, String::from("x=1.5*2.0;"));
+    
+    let mut scanner = lexer::Scanner::new_static(String::from("1e+ 99999999999 1e999"));
+    assert_eq!(scanner.scan().tag, lexer::Tag::Error);
+    assert_eq!(scanner.scan().tag, lexer::Tag::Error);
+    assert_eq!(scanner.scan().tag, lexer::Tag::Error);
+    let msgs : Vec<_> = scanner.errors.iter().map(|d| format!("{}", d)).collect();
+    assert_eq!(msgs, vec!["1:1: error[E0002]: malformed number `1e+`",
+                          "1:5: error[E0002]: integer literal `99999999999` is too large",
+                          "1:17: error[E0002]: real literal `1e999` is out of range"]);
+    
+    let listing = lexer::token_listing(lexer::Scanner::from_str("x = 1e999;"));
+    assert_eq!(listing, "1:1      Ide         x\n1:3      Assign      =\n\
+                         1:5      Error       1e999\n1:10     SemiColon   ;\n");
+}
+
+#[test]
+fn comment_test() {
+    let s = "x = 1; // set x\ny /* a /* nested */ comment */ = x / 2;";
+    assert_eq!(scan_string(String::from(s)), String::from("x=1;y=x/2;"));
+    
+    let mut scanner = lexer::Scanner::new_static(String::from(s));
+    while scanner.scan().tag != lexer::Tag::Eof {}
+    let comments : Vec<_> = scanner.comments.iter()
+        .map(|c| (c.kind, c.text.as_str(), c.span.lo, c.span.hi)).collect();
+    assert_eq!(comments, vec![
+        (lexer::CommentKind::Line, "// set x", 7, 15),
+        (lexer::CommentKind::Block, "/* a /* nested */ comment */", 18, 46),
+    ]);
+    
+    let mut scanner = lexer::Scanner::new_static(String::from("x /* a /* b */ y"));
+    assert_eq!(scanner.scan().tag, lexer::Tag::Ide);
+    let tok = scanner.scan();
+    assert_eq!(tok.tag, lexer::Tag::Error);
+    assert_eq!((tok.span.lo, tok.span.hi), (2, 16));
+    assert_eq!(scanner.scan().tag, lexer::Tag::Eof);
+    assert_eq!(format!("{}", scanner.errors[0]), "1:3: error[E0002]: unterminated block comment");
+}
+
+#[test]
+fn reader_test() {
+    let input : &[u8] = b"while (x) { y = 2.5; }";
+    let mut scanner = lexer::Scanner::from_reader(input).unwrap();
+    assert_eq!(scanner.source(), "while (x) { y = 2.5; }");
+    assert_eq!(scanner.scan().tag, lexer::Tag::While);
+    
+    let input : &[u8] = b"x = \xff;";
+    assert!(lexer::Scanner::from_reader(input).is_err());
+    assert!(lexer::Scanner::from_file("does/not/exist.src").is_err());
+}
+
+#[test]
+fn large_input_test() {
+    // Scanning must be linear in the size of the input.
+    let line = "{ int x; x = (x + 12345) * 3.5; /* comment */ }\n";
+    let n = 50000;
+    let mut s = String::new();
+    for _ in 0..n {
+        s.push_str(line);
+    }
+    let mut scanner = lexer::Scanner::from_str(&s);
+    let mut count = 0;
+    let mut tok = scanner.scan();
+    while tok.tag != lexer::Tag::Eof {
+        count += 1;
+        tok = scanner.scan();
+    }
+    assert_eq!(count, 15 * n);
+    assert_eq!(tok.span.line as usize, n + 1);
+}
+
+#[test]
+fn listing_test() {
+    let s = "{\n  float x;\n  x = .5 $ 2;\n}";
+    let expected = "\
+1:1      OpenBlock   {
+2:3      Type        type(float)
+2:9      Ide         x
+2:10     SemiColon   ;
+3:3      Ide         x
+3:5      Assign      =
+3:7      Real        0.5
+3:10     Error       $
+3:12     Num         2
+3:13     SemiColon   ;
+4:1      CloseBlock  }
+";
+    assert_eq!(lexer::token_listing(lexer::Scanner::from_str(s)), expected);
+}
 }
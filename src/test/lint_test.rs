@@ -0,0 +1,130 @@
+#[cfg(test)]
+mod test {
+    use lint::{check, LintLevels, Level};
+    use resolver::resolve;
+    use diagnostics::{Diagnostic, Severity};
+    use lexer::Scanner;
+    use parser::Parser;
+
+    fn lints(s : &str) -> Vec<Diagnostic> {
+        let mut program = Parser::new(Scanner::from_str(s)).parse_program().unwrap();
+        let decls = resolve(&mut program).unwrap();
+        LintLevels::new().apply(check(&program, &decls))
+    }
+
+    // Lint and message of each report of the given lint.
+    fn reports(s : &str, name : &str) -> Vec<String> {
+        lints(s).into_iter()
+            .filter(|d| d.code == Some(name))
+            .map(|d| d.message)
+            .collect()
+    }
+
+    #[test]
+    fn unused_variables_test() {
+        let s = "{int a; int b; int c; int[4] d; a = 1; d[a] = 2; {int b; c = b;}}";
+        assert_eq!(reports(s, "unused-variables"), vec![
+            "unused variable `b`",
+            "variable `c` is assigned to, but never read",
+            "variable `d` is assigned to, but never read",
+        ]);
+    }
+
+    #[test]
+    fn dead_stores_test() {
+        let s = "{int x; int y; x = 1; x = 2; y = x; y = y + 1; x = y;}";
+        let d = lints(s).into_iter().filter(|d| d.code == Some("dead-stores")).collect::<Vec<_>>();
+        assert_eq!(d.len(), 2);
+        assert_eq!(d[0].message, "value assigned to `x` is never read");
+        assert_eq!(d[0].primary.span.lo, 15);
+        assert_eq!(d[1].primary.span.lo, 47);
+        // Values read by the next iteration, after the loop or after a break
+        // are live.
+        let s = "{int i; int s; bool b; i = 0; s = 0; b = True;
+                  while (i < 10) { s = s + i; i = i + 1; if (s > 20) { b = False; break; } b = True; }
+                  if (b) i = s;
+                  s = i;}";
+        assert_eq!(reports(s, "dead-stores"), vec!["value assigned to `s` is never read"]);
+    }
+
+    #[test]
+    fn unreachable_code_test() {
+        let s = "{int i; bool b; i = 0; b = True;
+                  while (b) { if (b) break; else { i = 1; break; } i = 2; b = False; }
+                  while (b) { break; i = 3; }
+                  i = i + 1;}";
+        let d = lints(s).into_iter().filter(|d| d.code == Some("unreachable-code")).collect::<Vec<_>>();
+        assert_eq!(d.len(), 2);
+        assert_eq!(d[0].labels[0].message, "any code following this statement is unreachable");
+        assert_eq!(&s[d[0].primary.span.lo..d[0].primary.span.hi], "i = 2; b = False;");
+        assert_eq!(d[1].labels[0].message, "any code following this `break` is unreachable");
+    }
+
+    #[test]
+    fn function_test() {
+        // Bodies are checked like the program block, a return ends a path.
+        let s = "int f(int n) { int m; m = n; n = 2; return m; m = 3; } {int a; a = f(1);}";
+        assert_eq!(reports(s, "dead-stores"), vec![
+            "value assigned to `n` is never read",
+            "value assigned to `m` is never read",
+        ]);
+        let d = lints(s).into_iter().filter(|d| d.code == Some("unreachable-code")).collect::<Vec<_>>();
+        assert_eq!(d.len(), 1);
+        assert_eq!(d[0].labels[0].message, "any code following this `return` is unreachable");
+    }
+
+    #[test]
+    fn globals_test() {
+        // A call may read a global: stores to globals are never dead.
+        let s = "int g; const int N = 1; const int M = 2; int f() { return g; }
+                 {int a; g = 1; a = f(); g = 2; a = a + N;}";
+        assert_eq!(reports(s, "dead-stores"), vec!["value assigned to `a` is never read"]);
+        assert_eq!(reports(s, "unused-variables"), vec!["unused constant `M`"]);
+    }
+
+    #[test]
+    fn constant_conditions_test() {
+        let s = "{int i; i = 1;
+                  while (False) i = 2;
+                  while (1 > 2 && True) i = 3;
+                  if (7 / 2 == 3) i = 4;
+                  if (!(1.5 > 2) || i > 0) i = 5;
+                  if (-(2 - 3) * 2.0 != 2.0) i = 6; else i = i + 1;
+                  while (True) break;}";
+        assert_eq!(reports(s, "while-false").len(), 2);
+        // True or anything is true.
+        assert_eq!(reports(s, "constant-condition"), vec![
+            "this condition is always true",
+            "this condition is always true",
+            "this condition is always false",
+        ]);
+        // Ints wrap around as they do at run time.
+        let s = "{int x; if (2147483647 + 1 > 0) x = 1; else x = 2; x = x;}";
+        assert_eq!(reports(s, "constant-condition"), vec!["this condition is always false"]);
+    }
+
+    #[test]
+    fn levels_test() {
+        let s = "{int a; int b; while (False) b = 1;}";
+        let mut program = Parser::new(Scanner::from_str(s)).parse_program().unwrap();
+        let decls = resolve(&mut program).unwrap();
+        let mut levels = LintLevels::new();
+        levels.set("unused-variables", Level::Deny).unwrap();
+        levels.set("while-false", Level::Allow).unwrap();
+        assert_eq!(levels.set("unused", Level::Allow), Err(String::from("unknown lint `unused`")));
+        let d = levels.apply(check(&program, &decls));
+        assert_eq!(d.iter().map(|d| d.severity).collect::<Vec<_>>(), vec![Severity::Error, Severity::Error]);
+        assert_eq!(d[1].message, "variable `b` is assigned to, but never read");
+        assert_eq!(levels.level("dead-stores"), Level::Warn);
+    }
+
+    #[test]
+    fn switch_test() {
+        // A switch on a bool with both cases needs no default.
+        let s = "{int i; bool b; i = 1; b = True;
+                  switch (i) { case 1: i = 2; } while (b) switch (b) { case True: i = 3; case False: i = 4; break; i = 5; }
+                  switch (i) { default: b = False; } i = i; b = b;}";
+        assert_eq!(reports(s, "missing-default"), vec!["`switch` without a `default` case"]);
+        assert_eq!(reports(s, "unreachable-code"), vec!["unreachable statement"]);
+    }
+}
@@ -3,3 +3,14 @@
 */
 pub mod lexer_test;
 pub mod parser_test;
+pub mod diagnostics_test;
+pub mod interpreter_test;
+pub mod cst_test;
+pub mod formatter_test;
+pub mod visit_test;
+pub mod serialize_test;
+pub mod cfg_test;
+pub mod resolver_test;
+pub mod typeck_test;
+pub mod uninit_test;
+pub mod lint_test;
@@ -1,9 +1,12 @@
 #[cfg(test)]
 mod test {
-    use ast::expression::{BoolExpr as B, NumExpr as N, Relop, Loc};
+    use ast::expression::{BoolExpr as B, NumExpr as N, Loc, Relop};
+    use ast::expression::{BoolExprKind as BK, NumExprKind as NK, LocKind};
     use ast::statement::{Program, Block, Type, Statement as S, Decl as D, BasicType};
+    use ast::statement::StatementKind as SK;
     use parser::ParseNode;
     use lexer;
+    use lexer::Span;
     use parser;
         
     type SAst = Box<S>;
@@ -13,100 +16,119 @@ mod test {
     fn parse_string(s : &str) -> Option<Box<Program>> {
         let scanner = lexer::Scanner::new_static(String::from(s));
         let mut parser = parser::Parser::new(scanner);
-        parser.parse();
+        parser.parse().unwrap();
         parser.ast_root
     }
     
     fn parse_stmt(s : &str) -> Box<S> {
         let scanner = lexer::Scanner::new_static(String::from(s));
         let mut parser = parser::Parser::new(scanner);
-        let res = S::parse(&mut parser);
+        let res = S::parse(&mut parser).unwrap();
         res
     }
       
     fn parse_bool(s : &str) -> BAst {
         let scanner = lexer::Scanner::new_static(String::from(s));
         let mut parser = parser::Parser::new(scanner);
-        let res = B::parse(&mut parser);
+        let res = B::parse(&mut parser).unwrap();
         res
     }
     
     fn parse_expr(s : &str) -> NAst {
         let scanner = lexer::Scanner::new_static(String::from(s));
         let mut parser = parser::Parser::new(scanner);
-        let res = N::parse(&mut parser);
+        let res = N::parse(&mut parser).unwrap();
         res
     }
     
     fn parse_decl(s : &str) -> Box<D> {
         let scanner = lexer::Scanner::new_static(String::from(s));
         let mut parser = parser::Parser::new(scanner);
-        let res = D::parse(&mut parser);
+        let res = D::parse(&mut parser).unwrap();
         res
     }
     
     fn nbox<T>(x : T) -> Box<T> { Box::new(x) }
-    fn ast_ide(s : &str) -> NAst { Box::new(N::Loc(Box::new(Loc::Ide(String::from(s))))) }
-    fn ast_num(x : u32) -> NAst { Box::new(N::Num(x)) }    
-    fn bnumexpr(x : NAst) -> BAst { nbox(B::NumExpr(x)) }
-    fn add(e1 : NAst, e2 : NAst) -> NAst { nbox(N::Add(e1, e2)) }
-    fn btrue() -> BAst { nbox(B::NumExpr(nbox(N::True))) }
+    fn sbox(x : SK) -> SAst { S::new(x, Span::default()) }
+    fn bbox(x : BK) -> BAst { B::new(x, Span::default()) }
+    fn ebox(x : NK) -> NAst { N::new(x, Span::default()) }
+    fn lbox(x : LocKind) -> Box<Loc> { Loc::new(x, Span::default()) }
+    fn ast_ide(s : &str) -> NAst { ebox(NK::Loc(lbox(LocKind::Ide(String::from(s))))) }
+    fn ast_num(x : u32) -> NAst { ebox(NK::Num(x)) }    
+    fn bnumexpr(x : NAst) -> BAst { bbox(BK::NumExpr(x)) }
+    fn add(e1 : NAst, e2 : NAst) -> NAst { ebox(NK::Add(e1, e2)) }
+    fn btrue() -> BAst { bbox(BK::NumExpr(ebox(NK::True))) }
     
     fn assign() -> Box<S> { 
-        nbox(S::Assign(nbox(
-            Loc::Ide(String::from("y"))),
+        sbox(SK::Assign(
+            lbox(LocKind::Ide(String::from("y"))),
             bnumexpr(ast_ide("x"))
         )) 
     }
     
+    fn decl(type_id : Box<Type>, id : &str) -> Box<D> {
+        nbox(D {
+            type_id,
+            id : nbox(String::from(id)),
+            span : Span::default(),
+        })
+    }
+    
     fn prog(decls : Vec<Box<D>>, stmts : Vec<Box<S>>) -> Option<Box<Program>> { 
         Some(nbox( Program {
-            block : Box::new(Block {
+            items : Vec::new(),
+            block : Some(Box::new(Block {
                 decls : decls,
                 stmts : stmts,
-            })
+                span  : Span::default(),
+            })),
+            span : Span::default(),
         })) 
     }
     
     #[test]
     fn numexpr_test() {
         let s = "1+3";  // Add
-        let ast = nbox(N::Add(ast_num(1), ast_num(3)));
+        let ast = ebox(NK::Add(ast_num(1), ast_num(3)));
         assert_eq!(ast, parse_expr(s));
         
         let s = "-1*y"; // Mul, Minus
-        let ast = nbox(N::Mul(
-            nbox(N::Minus(ast_num(1))),
+        let ast = ebox(NK::Mul(
+            ebox(NK::Minus(ast_num(1))),
             ast_ide("y")
         ));
         assert_eq!(ast, parse_expr(s));
         
         let s = "(1/!y)"; // Not, Div, SubExpr
-        let ast = nbox(N::Expr(nbox(B::NumExpr(nbox(N::Div(
+        let ast = ebox(NK::Expr(bbox(BK::NumExpr(ebox(NK::Div(
             ast_num(1),
-            nbox(N::Not(ast_ide("y")))
+            ebox(NK::Not(ast_ide("y")))
         ))))));
         assert_eq!(ast, parse_expr(s));
+        
+        let s = "2.5*x"; // Real
+        let ast = ebox(NK::Mul(ebox(NK::Real(2.5)), ast_ide("x")));
+        assert_eq!(ast, parse_expr(s));
     }
     
     #[test]
     fn bool_test() {
-        let btrue = nbox(B::NumExpr(nbox(N::True)));
-        let bfalse = nbox(B::NumExpr(nbox(N::False)));
+        let btrue = bbox(BK::NumExpr(ebox(NK::True)));
+        let bfalse = bbox(BK::NumExpr(ebox(NK::False)));
         
         // True, False, Or
         let s = "True || False"; 
-        let ast = nbox(B::Or(
+        let ast = bbox(BK::Or(
             btrue, bfalse
         ));
         assert_eq!(ast, parse_bool(s));
         
         // And, Eq        
         let s = "y && (3==5)";
-        let ast = nbox(B::And(
+        let ast = bbox(BK::And(
             bnumexpr(ast_ide("y")),
-            bnumexpr(nbox(N::Expr(
-                nbox(B::Eq(
+            bnumexpr(ebox(NK::Expr(
+                bbox(BK::Eq(
                     bnumexpr(ast_num(3)), 
                     bnumexpr(ast_num(5))
                 ))
@@ -116,10 +138,10 @@ mod test {
         
         // Neq, Leq        
         let s = "(3!=5) <= y";
-        let ast = nbox(B::Relop(
+        let ast = bbox(BK::Relop(
             Relop::Leq,
-            nbox(N::Expr(
-                nbox(B::Neq(
+            ebox(NK::Expr(
+                bbox(BK::Neq(
                     bnumexpr(ast_num(3)), 
                     bnumexpr(ast_num(5))
                 ))
@@ -137,10 +159,7 @@ mod test {
             element_width : 4,
             dim_width     : vec![],                
         });   
-        let ast = nbox(D {
-            type_id : type_int,
-            id : nbox(String::from("x")),
-        });
+        let ast = decl(type_int, "x");
         assert_eq!(ast, parse_decl(s));
         
         let s = "float[3] x;";
@@ -149,10 +168,7 @@ mod test {
             element_width : 4,
             dim_width     : vec![12, 4],                
         };   
-        let ast = nbox(D {
-            type_id : nbox(type_float_arr),
-            id : nbox(String::from("x")),
-        });
+        let ast = decl(nbox(type_float_arr), "x");
         assert_eq!(ast, parse_decl(s));
     }
     
@@ -165,7 +181,7 @@ mod test {
         
         // IfThenElse
         let s = "if(True) y=x; else y=x;";
-        let ast = nbox(S::IfElse(
+        let ast = sbox(SK::IfElse(
             btrue(),
             assign(),
             assign()
@@ -174,11 +190,19 @@ mod test {
         
         // While
         let s = "while(True) y=x;";
-        let ast = nbox(S::While(
+        let ast = sbox(SK::While(
             btrue(),
             assign(),
         ));
         assert_eq!(ast, parse_stmt(s));
+
+        // Labeled loop
+        let s = "l: while(True) continue l;";
+        let ast = sbox(SK::Labeled(
+            String::from("l"),
+            sbox(SK::While(btrue(), sbox(SK::Continue(Some(String::from("l")))))),
+        ));
+        assert_eq!(ast, parse_stmt(s));
     }
     
     #[test]
@@ -190,15 +214,112 @@ mod test {
             dim_width     : vec![],                
         });   
         let ast = prog(
-            vec![nbox(D {
-                type_id : type_int,
-                id : nbox(String::from("x")),
-            })],
-            vec![nbox(S::Assign(
-                nbox(Loc::Ide(String::from("y"))),
+            vec![decl(type_int, "x")],
+            vec![sbox(SK::Assign(
+                lbox(LocKind::Ide(String::from("y"))),
                 bnumexpr(ast_ide("x"))
             ))]
         );
         assert_eq!(ast, parse_string(s));
     }
+    
+    #[test]
+    fn span_test() {
+        let s = "{int x;\n  y = x + 1;\n}";
+        let ast = parse_string(s).unwrap();
+        assert_eq!((ast.span.lo, ast.span.hi), (0, s.len()));
+        
+        let block = ast.block.as_ref().unwrap();
+        let d = &block.decls[0];
+        assert_eq!((d.span.lo, d.span.hi, d.span.line, d.span.col), (1, 7, 1, 2));
+        
+        let st = &block.stmts[0];
+        assert_eq!((st.span.lo, st.span.hi, st.span.line, st.span.col), (10, 20, 2, 3));
+        if let SK::Assign(ref l, ref b) = st.kind {
+            assert_eq!((l.span.lo, l.span.hi), (10, 11));
+            assert_eq!((b.span.lo, b.span.hi), (14, 19));
+        } else { panic!("Expected an assignment.") }
+    }
+    
+    #[test]
+    fn error_test() {
+        let scanner = lexer::Scanner::new_static(String::from("{int x; x = 1 }"));
+        let mut parser = parser::Parser::new(scanner);
+        let errors = parser.parse().unwrap_err();
+        let err = &errors[0];
+        assert_eq!(err.expected, "`;`");
+        assert_eq!(err.found.tag, lexer::Tag::CloseBlock);
+        assert_eq!((err.span.line, err.span.col), (1, 15));
+        
+        let scanner = lexer::Scanner::new_static(String::from("x = ;"));
+        let mut parser = parser::Parser::new(scanner);
+        let err = S::parse(&mut parser).unwrap_err();
+        assert_eq!(format!("{}", err), "1:5: expected expression, found `;`");
+        
+        let scanner = lexer::Scanner::new_static(String::from("int 3;"));
+        let mut parser = parser::Parser::new(scanner);
+        assert_eq!(D::parse(&mut parser).unwrap_err().expected, "identifier");
+        
+        // Types are shown as written.
+        let scanner = lexer::Scanner::new_static(String::from("{int x float y;}"));
+        let mut parser = parser::Parser::new(scanner);
+        let errors = parser.parse().unwrap_err();
+        assert_eq!(format!("{}", errors[0]), "1:8: expected `;`, found `float`");
+        
+        // Case labels must fit in an int.
+        let scanner = lexer::Scanner::new_static(String::from("switch (x) { case 3000000000: }"));
+        let mut parser = parser::Parser::new(scanner);
+        let err = S::parse(&mut parser).unwrap_err();
+        assert_eq!(format!("{}", err), "1:19: case label out of range");
+        assert_eq!((err.span.lo, err.span.hi), (18, 28));
+        let scanner = lexer::Scanner::new_static(String::from("switch (x) { case -2147483649: }"));
+        let mut parser = parser::Parser::new(scanner);
+        let err = S::parse(&mut parser).unwrap_err();
+        assert_eq!(err.to_diagnostic().message, "case label out of range");
+        assert_eq!((err.span.lo, err.span.hi), (18, 29));
+    }
+    
+    #[test]
+    fn recovery_test() {
+        let s = "{int x; x = ; y = 3 4; int; if (x) y = x; x = (1 + ; }";
+        let scanner = lexer::Scanner::new_static(String::from(s));
+        let mut parser = parser::Parser::new(scanner);
+        let errors = parser.parse().unwrap_err();
+        let found : Vec<_> = errors.iter().map(|e| (e.expected, e.span.col)).collect();
+        assert_eq!(found, vec![("expression", 13), ("`;`", 21), ("identifier", 27),
+                               ("expression", 52)]);
+        
+        let type_int = nbox(Type {
+            basic_type    : BasicType::Int,
+            element_width : 4,
+            dim_width     : vec![],                
+        });
+        let error = || sbox(SK::Error);
+        let ast = prog(
+            vec![decl(type_int, "x")],
+            vec![error(), error(), error(),
+                 sbox(SK::If(bnumexpr(ast_ide("x")), assign())),
+                 error()]
+        );
+        assert_eq!(ast, parser.ast_root);
+        
+        // Missing braces are reported but the statements are still parsed.
+        let scanner = lexer::Scanner::new_static(String::from("y = x;"));
+        let mut parser = parser::Parser::new(scanner);
+        let errors = parser.parse().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(prog(vec![], vec![assign()]), parser.ast_root);
+        
+        // Globals and functions are skipped one at a time.
+        let s = "int x int y; int f(int a b) { int z; } const int c = 1; void main() { }";
+        let scanner = lexer::Scanner::new_static(String::from(s));
+        let mut parser = parser::Parser::new(scanner);
+        let errors = parser.parse().unwrap_err();
+        let found : Vec<_> = errors.iter().map(|e| format!("{}", e)).collect();
+        assert_eq!(found, vec!["1:7: expected `;`, found `int`",
+                               "1:26: expected `)`, found `b`"]);
+        let root = parser.ast_root.unwrap();
+        assert_eq!(root.items.len(), 3);
+        assert!(root.block.is_none());
+    }
 }
@@ -0,0 +1,178 @@
+#[cfg(test)]
+mod test {
+    use resolver::{resolve, DeclInfo};
+    use ast::visit::{Visitor, walk_loc};
+    use ast::expression::{Loc, LocKind};
+    use ast::statement::{Program, DeclId};
+    use diagnostics::Diagnostic;
+    use lexer::Scanner;
+    use parser::Parser;
+
+    fn check(s : &str) -> (Box<Program>, Result<Vec<DeclInfo>, Vec<Diagnostic>>) {
+        let mut program = Parser::new(Scanner::from_str(s)).parse_program().unwrap();
+        let res = resolve(&mut program);
+        (program, res)
+    }
+
+    // Name and declaration of each location, in visiting order.
+    struct Bindings(Vec<(String, Option<DeclId>)>);
+
+    impl Visitor for Bindings {
+        fn visit_loc(&mut self, l : &Loc) {
+            match l.kind {
+                LocKind::Ide(ref s) | LocKind::Index(ref s, _) => self.0.push((s.clone(), l.decl)),
+            }
+            walk_loc(self, l);
+        }
+    }
+
+    fn bindings(p : &Program) -> Vec<(String, Option<DeclId>)> {
+        let mut b = Bindings(Vec::new());
+        b.visit_program(p);
+        b.0
+    }
+
+    #[test]
+    fn outer_lookup_test() {
+        let (p, res) = check("{int x; {int y; y = x; {x = y;}}}");
+        let decls = res.unwrap();
+        assert_eq!(decls.iter().map(|d| &*d.name).collect::<Vec<_>>(), vec!["x", "y"]);
+        assert_eq!(bindings(&p), vec![
+            (String::from("y"), Some(DeclId(1))),
+            (String::from("x"), Some(DeclId(0))),
+            (String::from("x"), Some(DeclId(0))),
+            (String::from("y"), Some(DeclId(1))),
+        ]);
+    }
+
+    #[test]
+    fn shadowing_test() {
+        // The inner declaration hides the outer one only inside its block.
+        let (p, res) = check("{int x; {float x; x = 1.0;} x = 2;}");
+        let decls = res.unwrap();
+        assert_eq!(decls.len(), 2);
+        assert_eq!(decls[1].span.lo, 9);
+        assert_eq!(bindings(&p), vec![
+            (String::from("x"), Some(DeclId(1))),
+            (String::from("x"), Some(DeclId(0))),
+        ]);
+    }
+
+    #[test]
+    fn undeclared_test() {
+        // All the errors are reported, not only the first one.
+        let (_, res) = check("{int a; {int b;} b = a; a = c[b];}");
+        let diags = res.unwrap_err();
+        assert_eq!(diags.len(), 3);
+        assert_eq!(diags[0].message, "undeclared identifier `b`");
+        assert_eq!(diags[0].primary.span.lo, 17);
+        assert_eq!(diags[1].message, "undeclared identifier `c`");
+        assert_eq!(diags[2].message, "undeclared identifier `b`");
+        assert_eq!(diags[2].primary.span.lo, 30);
+    }
+
+    #[test]
+    fn duplicate_test() {
+        let (_, res) = check("{int a; float a; {int a;}}");
+        let diags = res.unwrap_err();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "`a` is already declared in this block");
+        assert_eq!(diags[0].code, Some("E0102"));
+        assert_eq!(diags[0].primary.span.lo, 8);
+        assert_eq!(diags[0].labels[0].span.lo, 1);
+    }
+
+    #[test]
+    fn break_outside_loop_test() {
+        let (_, res) = check("{int a; break; if (a > 0) { break; } while (a > 0) { { break; } }}");
+        let diags = res.unwrap_err();
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].message, "`break` outside of a loop");
+        assert_eq!(diags[0].code, Some("E0104"));
+        assert_eq!((diags[0].primary.span.lo, diags[1].primary.span.lo), (8, 28));
+    }
+
+    #[test]
+    fn for_scope_test() {
+        // The loop variable is only visible in the loop.
+        let (p, res) = check("{int i; for (float i = 0; i < 1; i = i + 1) i = 2; i = 3;}");
+        assert_eq!(res.unwrap().len(), 2);
+        let ids : Vec<_> = bindings(&p).into_iter().map(|(_, id)| id.unwrap().0).collect();
+        assert_eq!(ids, vec![1, 1, 1, 1, 1, 0]);
+        let (_, res) = check("{for (int i = 0; i < 1; i = i + 1) { } i = 3;}");
+        assert_eq!(res.unwrap_err()[0].message, "undeclared identifier `i`");
+    }
+
+    #[test]
+    fn label_test() {
+        let (_, res) = check("{int a; continue; l: while (a > 0) { break l; continue m; } break l;}");
+        let diags = res.unwrap_err();
+        assert_eq!(diags.len(), 3);
+        assert_eq!(diags[0].message, "`continue` outside of a loop");
+        assert_eq!(diags[1].message, "use of undeclared label `m`");
+        assert_eq!(diags[1].code, Some("E0105"));
+        assert_eq!(diags[2].message, "use of undeclared label `l`");
+    }
+
+    #[test]
+    fn duplicate_case_test() {
+        let (_, res) = check("{int a; switch (a) { case 1: a = 2; case -1: default: case 1: default: }}");
+        let diags = res.unwrap_err();
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].message, "duplicate case label `1`");
+        assert_eq!(diags[0].code, Some("E0106"));
+        assert_eq!((diags[0].primary.span.lo, diags[0].labels[0].span.lo), (54, 21));
+        assert_eq!(diags[1].message, "multiple `default` cases");
+        
+        // The smallest int is a valid label, and different from the largest.
+        let (_, res) = check("{int a; switch (a) { case -2147483648: case 2147483647: case -2147483648: }}");
+        let diags = res.unwrap_err();
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "duplicate case label `-2147483648`");
+    }
+
+    #[test]
+    fn function_test() {
+        // Functions are visible everywhere, their parameters only in the body.
+        let (_, res) = check("int f(int n) { return g(n); } int g(int m) { return f(m); } {int a; a = f(1);}");
+        assert!(res.is_ok());
+        let (_, res) = check("int f(int n) { return a; } void f() { } {int a; a = h(n); return;}");
+        let diags = res.unwrap_err();
+        assert_eq!(diags.len(), 5);
+        assert_eq!(diags[0].message, "function `f` is defined more than once");
+        assert_eq!(diags[0].code, Some("E0102"));
+        assert_eq!(diags[1].message, "undeclared identifier `a`");
+        assert_eq!(diags[2].message, "cannot find function `h`");
+        assert_eq!(diags[3].message, "undeclared identifier `n`");
+        assert_eq!(diags[4].message, "`return` outside of a function");
+        assert_eq!(diags[4].code, Some("E0107"));
+    }
+
+    #[test]
+    fn entry_point_test() {
+        let (_, res) = check("int g;");
+        assert_eq!(res.unwrap_err()[0].message, "`main` function not found");
+        let (_, res) = check("void main() { } {}");
+        let diags = res.unwrap_err();
+        assert_eq!(diags[0].message, "a program with a block cannot have a `main` function");
+        assert_eq!(diags[0].code, Some("E0108"));
+        let (_, res) = check("int main() { return 0; }");
+        assert_eq!(res.unwrap_err()[0].message, "`main` must take no parameters and return no value");
+    }
+
+    #[test]
+    fn global_test() {
+        // Globals are visible after their declaration, in all the functions.
+        let (p, res) = check("int g; const int N = g + 1; void f() { int g; g = N; } {g = N;}");
+        assert_eq!(res.unwrap().len(), 3);
+        let ids : Vec<_> = bindings(&p).into_iter().map(|(_, id)| id.unwrap().0).collect();
+        assert_eq!(ids, vec![0, 2, 1, 0, 1]);
+        let (_, res) = check("const int N = M; const int M = 1; int N; void main() { N = 2; }");
+        let diags = res.unwrap_err();
+        assert_eq!(diags.len(), 3);
+        assert_eq!(diags[0].message, "undeclared identifier `M`");
+        assert_eq!(diags[1].message, "`N` is already declared in this block");
+        assert_eq!(diags[2].message, "cannot assign to constant `N`");
+        assert_eq!(diags[2].code, Some("E0109"));
+    }
+}
@@ -0,0 +1,105 @@
+#[cfg(test)]
+mod test {
+    use ast::serialize::{to_json, to_sexp, to_dot};
+    use ast::statement::Program;
+    use ast::expression::{NumExpr, NumExprKind};
+    use ast::visit::{MutVisitor, walk_num_expr_mut};
+    use lexer::Scanner;
+    use parser::Parser;
+
+    fn parse(s : &str) -> Box<Program> {
+        Parser::new(Scanner::from_str(s)).parse_program().unwrap()
+    }
+
+    // Replaces the reals, in order.
+    struct SetReals(Vec<f64>);
+
+    impl MutVisitor for SetReals {
+        fn visit_num_expr(&mut self, e : &mut NumExpr) {
+            if let NumExprKind::Real(ref mut x) = e.kind {
+                *x = self.0.remove(0);
+            }
+            walk_num_expr_mut(self, e);
+        }
+    }
+
+    #[test]
+    fn sexp_test() {
+        let p = parse("{int[3] a; while (a[0] < .5) break;}");
+        let expected = "(Program :span (0 36 1 1) :block (Block :span (0 36 1 1) \
+            :decls ((Decl :span (1 10 1 2) :type (Type :basic Int :dims (3)) :name \"a\")) \
+            :stmts ((Statement While :span (11 35 1 12) \
+            :cond (BoolExpr Relop :span (18 27 1 19) :op Les \
+            :lhs (NumExpr Loc :span (18 22 1 19) :loc (Loc Index :span (18 22 1 19) :name \"a\" \
+            :indices ((BoolExpr NumExpr :span (20 21 1 21) :expr (NumExpr Num :span (20 21 1 21) :value 0))))) \
+            :rhs (NumExpr Real :span (25 27 1 26) :value 0.5)) \
+            :body (Statement Break :span (29 35 1 30))))))";
+        assert_eq!(to_sexp(&p), expected);
+    }
+
+    #[test]
+    fn json_test() {
+        let p = parse("{\nx = !(True || y);\n}");
+        let span = |lo, hi, line, col| format!("\"span\":{{\"lo\":{},\"hi\":{},\"line\":{},\"col\":{}}}", lo, hi, line, col);
+        let expected = format!("{{\"node\":\"Program\",{s0},\"block\":{{\"node\":\"Block\",{s0},\"decls\":[],\
+            \"stmts\":[{{\"node\":\"Statement\",\"kind\":\"Assign\",{s1},\
+            \"target\":{{\"node\":\"Loc\",\"kind\":\"Ide\",{s2},\"name\":\"x\"}},\
+            \"value\":{{\"node\":\"BoolExpr\",\"kind\":\"NumExpr\",{s3},\
+            \"expr\":{{\"node\":\"NumExpr\",\"kind\":\"Not\",{s3},\
+            \"operand\":{{\"node\":\"NumExpr\",\"kind\":\"Expr\",{s4},\
+            \"expr\":{{\"node\":\"BoolExpr\",\"kind\":\"Or\",{s5},\
+            \"lhs\":{{\"node\":\"BoolExpr\",\"kind\":\"NumExpr\",{s6},\"expr\":{{\"node\":\"NumExpr\",\"kind\":\"True\",{s6}}}}},\
+            \"rhs\":{{\"node\":\"BoolExpr\",\"kind\":\"NumExpr\",{s7},\"expr\":{{\"node\":\"NumExpr\",\"kind\":\"Loc\",{s7},\
+            \"loc\":{{\"node\":\"Loc\",\"kind\":\"Ide\",{s7},\"name\":\"y\"}}}}}}}}}}}}}}}}]}}}}",
+            s0 = span(0, 21, 1, 1), s1 = span(2, 19, 2, 1), s2 = span(2, 3, 2, 1),
+            s3 = span(6, 18, 2, 5), s4 = span(7, 18, 2, 6), s5 = span(8, 17, 2, 7),
+            s6 = span(8, 12, 2, 7), s7 = span(16, 17, 2, 15));
+        assert_eq!(to_json(&p), expected);
+    }
+
+    #[test]
+    fn dot_test() {
+        let p = parse("{ int[2] a; a[0] = -1; }");
+        let expected = "\
+digraph ast {
+    node [shape=box];
+    n0 [label=\"Program\\n1:1\"];
+    n1 [label=\"Block\\n1:1\"];
+    n2 [label=\"Decl\\n1:3\\nname: \\\"a\\\"\"];
+    n3 [label=\"Type\\nbasic: Int\\ndims: (2)\"];
+    n2 -> n3 [label=\"type\"];
+    n1 -> n2 [label=\"decls[0]\"];
+    n4 [label=\"Statement Assign\\n1:13\"];
+    n5 [label=\"Loc Index\\n1:13\\nname: \\\"a\\\"\"];
+    n6 [label=\"BoolExpr NumExpr\\n1:15\"];
+    n7 [label=\"NumExpr Num\\n1:15\\nvalue: 0\"];
+    n6 -> n7 [label=\"expr\"];
+    n5 -> n6 [label=\"indices[0]\"];
+    n4 -> n5 [label=\"target\"];
+    n8 [label=\"BoolExpr NumExpr\\n1:20\"];
+    n9 [label=\"NumExpr Minus\\n1:20\"];
+    n10 [label=\"NumExpr Num\\n1:21\\nvalue: 1\"];
+    n9 -> n10 [label=\"operand\"];
+    n8 -> n9 [label=\"expr\"];
+    n4 -> n8 [label=\"value\"];
+    n1 -> n4 [label=\"stmts[0]\"];
+    n0 -> n1 [label=\"block\"];
+}
+";
+        assert_eq!(to_dot(&p), expected);
+    }
+
+    #[test]
+    fn non_finite_test() {
+        let mut p = parse("{float x; x = 1.0 + 2.0 * 3.0;}");
+        SetReals(vec![f64::INFINITY, f64::NEG_INFINITY, f64::NAN]).visit_program(&mut p);
+        let json = to_json(&p);
+        assert_eq!(json.matches("\"kind\":\"Real\"").count(), 3);
+        assert_eq!(json.matches("\"value\":null").count(), 3);
+        let sexp = to_sexp(&p);
+        assert!(sexp.contains(":value +inf.0)"));
+        assert!(sexp.contains(":value -inf.0)"));
+        assert!(sexp.contains(":value +nan.0)"));
+        assert!(to_dot(&p).contains("value: +nan.0"));
+    }
+}
@@ -0,0 +1,121 @@
+#[cfg(test)]
+mod test {
+    use typeck::check;
+    use resolver::resolve;
+    use diagnostics::Diagnostic;
+    use lexer::Scanner;
+    use parser::Parser;
+
+    fn errors(s : &str) -> Vec<Diagnostic> {
+        let mut program = Parser::new(Scanner::from_str(s)).parse_program().unwrap();
+        let decls = resolve(&mut program).unwrap();
+        match check(&program, &decls) {
+            Ok(()) => Vec::new(),
+            Err(diags) => diags,
+        }
+    }
+
+    // Code and message of each error.
+    fn messages(s : &str) -> Vec<(&'static str, String)> {
+        errors(s).into_iter().map(|d| (d.code.unwrap(), d.message)).collect()
+    }
+
+    #[test]
+    fn promotion_test() {
+        let s = "{float x; int i; int[3] a; x = i + 1.5; x = i; x = a[i] * x; i = a[2] / 2;}";
+        assert_eq!(errors(s), Vec::new());
+    }
+
+    #[test]
+    fn assign_test() {
+        let s = "{int i; float x; i = x; i = 1 < 2; x = True;}";
+        assert_eq!(messages(s), vec![
+            ("E0201", String::from("mismatched types: expected `int`, found `float`")),
+            ("E0201", String::from("mismatched types: expected `int`, found `bool`")),
+            ("E0201", String::from("mismatched types: expected `float`, found `bool`")),
+        ]);
+        let d = &errors(s)[0];
+        assert_eq!((d.primary.span.lo, d.labels[0].span.lo), (21, 17));
+    }
+
+    #[test]
+    fn bool_operand_test() {
+        let s = "{int i; if (True + 1 > i) i = -(i < 2); if (i && !i) i = 0;}";
+        assert_eq!(messages(s), vec![
+            ("E0202", String::from("arithmetic on a value of type `bool`")),
+            ("E0202", String::from("arithmetic on a value of type `bool`")),
+            ("E0201", String::from("mismatched types: expected `bool`, found `int`")),
+            ("E0201", String::from("mismatched types: expected `bool`, found `int`")),
+        ]);
+        let d = &errors("{int i; if (i == (i < 1)) i = 0;}")[0];
+        assert_eq!(d.message, "cannot compare `int` with `bool`");
+        assert_eq!(d.labels.len(), 2);
+    }
+
+    #[test]
+    fn array_test() {
+        let s = "{int[10][2] a; int i; float x; i = a[1]; a = 0; i = a[x][1]; x = i[0];}";
+        assert_eq!(messages(s), vec![
+            ("E0103", String::from("`a` has 2 dimensions but is indexed with 1")),
+            ("E0103", String::from("`a` has 2 dimensions but is indexed with 0")),
+            ("E0201", String::from("mismatched types: expected `int`, found `float`")),
+            ("E0103", String::from("`i` has 0 dimensions but is indexed with 1")),
+        ]);
+    }
+
+    #[test]
+    fn condition_test() {
+        let s = "{int i; bool b; if (i) i = 1; while (b) b = i; b = i > 0 || b == !b; if (b) i = 2;}";
+        assert_eq!(messages(s), vec![
+            ("E0201", String::from("mismatched types: expected `bool`, found `int`")),
+            ("E0201", String::from("mismatched types: expected `bool`, found `int`")),
+        ]);
+        assert_eq!(errors(s)[0].primary.span.lo, 20);
+    }
+
+    #[test]
+    fn switch_test() {
+        let s = "{int i; float f; bool b; switch (i) { case 1: i = 2; case True: i = 3; }
+                  switch (f) { default: i = 1; } switch (b) { case False: b = True; case 0: b = False; }}";
+        assert_eq!(messages(s), vec![
+            ("E0201", String::from("mismatched types: expected `int`, found `bool`")),
+            ("E0201", String::from("mismatched types: expected `int`, found `float`")),
+            ("E0201", String::from("mismatched types: expected `bool`, found `int`")),
+        ]);
+    }
+
+    #[test]
+    fn call_test() {
+        let s = "float f(float x, int n) { return n; } void p() { }
+                 {float x; x = f(1, 2); x = f(1); x = f(True, 2); x = p(); p(2);}";
+        assert_eq!(messages(s), vec![
+            ("E0203", String::from("`f` takes 2 arguments but 1 was supplied")),
+            ("E0201", String::from("mismatched types: expected `float`, found `bool`")),
+            ("E0204", String::from("`p` does not return a value")),
+            ("E0203", String::from("`p` takes 0 arguments but 1 was supplied")),
+        ]);
+    }
+
+    #[test]
+    fn const_test() {
+        let s = "const float F = 1; const bool B = 1 < 2; const int I = F; {}";
+        assert_eq!(messages(s), vec![
+            ("E0201", String::from("mismatched types: expected `int`, found `float`")),
+        ]);
+    }
+
+    #[test]
+    fn return_test() {
+        let s = "int f(bool b) { if (b) return 1; else { return 2.5; } }
+                 int g(bool b) { if (b) return 1; }
+                 int h(int i) { switch (i) { case 1: return 1; default: return; } }
+                 void p() { return 1; }
+                 {}";
+        assert_eq!(messages(s), vec![
+            ("E0201", String::from("mismatched types: expected `int`, found `float`")),
+            ("E0205", String::from("function `g` may end without returning a value")),
+            ("E0205", String::from("`return` without a value in `h`")),
+            ("E0204", String::from("`p` does not return a value")),
+        ]);
+    }
+}
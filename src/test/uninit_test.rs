@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod test {
+    use uninit::check;
+    use resolver::resolve;
+    use code_generator::CodeGenerator;
+    use diagnostics::{Diagnostic, Severity};
+    use lint::Level;
+    use lexer::Scanner;
+    use parser::Parser;
+
+    fn warnings(s : &str) -> Vec<Diagnostic> {
+        let mut program = Parser::new(Scanner::from_str(s)).parse_program().unwrap();
+        let decls = resolve(&mut program).unwrap();
+        check(&program, &decls)
+    }
+
+    // Names reported, in order.
+    fn reported(s : &str) -> Vec<String> {
+        warnings(s).into_iter()
+            .map(|d| d.message.split('`').nth(1).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn straight_line_test() {
+        let s = "{int x; int y; x = 1; y = x + y; x = y * y;}";
+        let w = warnings(s);
+        assert_eq!(w.len(), 1);
+        assert_eq!(w[0].severity, Severity::Warning);
+        assert_eq!(w[0].message, "`y` may be used before it is assigned");
+        assert_eq!(w[0].code, Some("uninitialized"));
+        assert_eq!((w[0].primary.span.lo, w[0].labels[0].span.lo), (30, 8));
+    }
+
+    #[test]
+    fn branches_test() {
+        // Assigned by both branches, by only one, and by none.
+        let s = "{bool b; int x; int y; int z; b = True;
+                  if (b) { x = 1; y = 1; } else x = 2;
+                  if (b) z = 1;
+                  x = x + y + z;}";
+        assert_eq!(reported(s), vec!["y", "z"]);
+        // A branch ending with break does not reach the merge.
+        let s = "{bool b; int x; b = True;
+                  while (b) { if (b) break; else x = 1; b = x > 0; }}";
+        assert_eq!(reported(s), Vec::<String>::new());
+    }
+
+    #[test]
+    fn loop_test() {
+        // The body may not run, and the condition is read before it.
+        let s = "{int i; int n; int s; i = 0; n = 3;
+                  while (i < n) { s = i; i = i + 1; }
+                  n = s; while (i < 10) { i = s + 1; { int t; s = t; } }}";
+        assert_eq!(reported(s), vec!["s", "t"]);
+        // Arrays are not tracked.
+        assert_eq!(reported("{int[3] a; int x; x = a[0];}"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn do_for_test() {
+        // The body of a do runs once, but a continue skips the rest of it.
+        let s = "{int x; int y; int z; do { x = 1; } while (x < 0); y = x;
+                  do { if (y > 0) continue; z = 1; } while (z < 0); y = z;}";
+        assert_eq!(reported(s), vec!["z"]);
+        // The step runs after the body, and only a break leaves a for without condition.
+        let s = "{int x; int y; for (int i = 0; i < 3; i = i + x) { x = i; }
+                  for (;;) { y = 1; break; } x = y;}";
+        assert_eq!(reported(s), Vec::<String>::new());
+        assert_eq!(reported("{int x; for (int i = 0; i < 3; i = i + x) { if (i > 1) continue; x = i; }}"),
+                   vec!["x"]);
+    }
+
+    #[test]
+    fn function_test() {
+        // Parameters are assigned by the call, nothing is assigned after a return.
+        let s = "int f(int n) { int m; if (n > 0) return m; else m = 1; return n + m; } {int a; a = f(a);}";
+        assert_eq!(reported(s), vec!["m", "a"]);
+    }
+
+    #[test]
+    fn globals_test() {
+        // Globals and constants have a value before the program starts.
+        let s = "int g; const int N = 2; int f() { return g + N; } {int a; a = g + f();}";
+        assert_eq!(reported(s), Vec::<String>::new());
+    }
+
+    #[test]
+    fn deny_test() {
+        let s = "{int x; int y; y = x; x = y;}";
+        let mut code_gen = CodeGenerator::new(Parser::new(Scanner::from_str(s)));
+        assert_eq!(code_gen.generate_code(), Ok(()));
+        assert_eq!(code_gen.diagnostics.iter().filter(|d| d.code == Some("uninitialized")).count(), 1);
+        let mut code_gen = CodeGenerator::new(Parser::new(Scanner::from_str(s)));
+        code_gen.lints.set("uninitialized", Level::Deny).unwrap();
+        let errors = code_gen.generate_code().unwrap_err();
+        assert!(errors[0].is_error());
+    }
+}
@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod test {
+    use ast::visit::{Visitor, MutVisitor, walk_num_expr_mut, walk_loc};
+    use ast::expression::{NumExpr, NumExprKind as NK, Loc, LocKind};
+    use ast::statement::{Program, Decl};
+    use lexer::Scanner;
+    use parser::Parser;
+
+    fn parse(s : &str) -> Box<Program> {
+        Parser::new(Scanner::from_str(s)).parse_program().unwrap()
+    }
+
+    // Names of the locations used, in visiting order.
+    struct Uses {
+        names : Vec<String>,
+        decls : usize,
+    }
+
+    impl Visitor for Uses {
+        fn visit_decl(&mut self, _d : &Decl) {
+            self.decls += 1;
+        }
+
+        fn visit_loc(&mut self, l : &Loc) {
+            match l.kind {
+                LocKind::Ide(ref s) | LocKind::Index(ref s, _) => self.names.push(s.clone()),
+            }
+            walk_loc(self, l);
+        }
+    }
+
+    // Folds additions and multiplications of integer constants.
+    struct ConstFolder;
+
+    impl MutVisitor for ConstFolder {
+        fn visit_num_expr(&mut self, e : &mut NumExpr) {
+            walk_num_expr_mut(self, e);
+            let folded = match e.kind {
+                NK::Add(ref a, ref b) | NK::Mul(ref a, ref b) => match (&a.kind, &b.kind) {
+                    (&NK::Num(x), &NK::Num(y)) => {
+                        if let NK::Add(..) = e.kind { Some(x + y) } else { Some(x * y) }
+                    },
+                    _ => None,
+                },
+                _ => None,
+            };
+            if let Some(x) = folded {
+                e.kind = NK::Num(x);
+            }
+        }
+    }
+
+    struct Rename<'a>(&'a str, &'a str);
+
+    impl<'a> MutVisitor for Rename<'a> {
+        fn visit_decl(&mut self, d : &mut Decl) {
+            if *d.id == self.0 {
+                *d.id = String::from(self.1);
+            }
+        }
+
+        fn visit_loc(&mut self, l : &mut Loc) {
+            if let LocKind::Ide(ref mut s) = l.kind {
+                if s == self.0 {
+                    *s = String::from(self.1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn visitor_test() {
+        let p = parse("{ int x; int[2] a; x = a[y] + 1; while (x < z) { if (w) x = 1; else break; } }");
+        let mut uses = Uses { names : vec![], decls : 0 };
+        uses.visit_program(&p);
+        assert_eq!(uses.names, vec!["x", "a", "y", "x", "z", "w", "x"]);
+        assert_eq!(uses.decls, 2);
+    }
+
+    #[test]
+    fn mut_visitor_test() {
+        let mut p = parse("{ x = 2 * (1 + 3) + y; if (x < 2 + 2) x = 4 * 5; }");
+        ConstFolder.visit_program(&mut p);
+        Rename("x", "z").visit_program(&mut p);
+        assert_eq!(p, parse("{ z = 2 * (4) + y; if (z < 4) z = 20; }"));
+    }
+}
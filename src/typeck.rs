@@ -0,0 +1,399 @@
+/*
+    Type checking: gives a type to every expression and checks that operators
+    and assignments are applied to values of the right type. The only
+    implicit conversion is from int to float, also when passing arguments and
+    returning values. Runs after the resolver, on the declarations it found.
+*/
+use std::fmt;
+use std::collections::HashMap;
+use ast::statement::{Program, Item, Function, Statement, StatementKind, BasicType, CaseLabel, covers_all};
+use ast::expression::{BoolExpr, BoolExprKind, NumExpr, NumExprKind, Loc, LocKind};
+use ast::visit::{Visitor, walk_program, walk_function, walk_statement};
+use resolver::DeclInfo;
+use lexer::Span;
+use diagnostics::{Diagnostic, E_ARRAY_DIM, E_MISMATCHED_TYPES, E_NOT_NUMERIC, E_ARG_COUNT, E_NO_VALUE,
+                  E_MISSING_RETURN};
+
+// Type of an expression. Arrays are always indexed down to their elements.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Ty {
+    Int,
+    Float,
+    Bool,
+}
+
+impl Ty {
+    fn of(t : BasicType) -> Self {
+        match t {
+            BasicType::Int => Ty::Int,
+            BasicType::Float => Ty::Float,
+            BasicType::Bool => Ty::Bool,
+        }
+    }
+
+    pub fn is_numeric(self) -> bool {
+        self == Ty::Int || self == Ty::Float
+    }
+
+    // Can a value of type from be stored where a self is expected?
+    pub fn accepts(self, from : Ty) -> bool {
+        self == from || (self == Ty::Float && from == Ty::Int)
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        let s = match *self {
+            Ty::Int => "int",
+            Ty::Float => "float",
+            Ty::Bool => "bool",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+pub fn check(program : &Program, decls : &[DeclInfo]) -> Result<(), Vec<Diagnostic>> {
+    let mut c = TypeChecker::new(decls);
+    c.visit_program(program);
+    if c.diagnostics.is_empty() {
+        Ok(())
+    } else {
+        Err(c.diagnostics)
+    }
+}
+
+// The type of an expression is None when it has an error, already reported:
+// the expressions around it are not checked again.
+pub struct TypeChecker<'a> {
+    decls           : &'a [DeclInfo],
+    functions       : HashMap<String, Signature>,
+    function        : Option<(String, Option<Ty>)>,    // Name and return type.
+    pub diagnostics : Vec<Diagnostic>,
+}
+
+// Types of the parameters and of the value returned, None for procedures.
+struct Signature {
+    params : Vec<Ty>,
+    ret    : Option<Ty>,
+    span   : Span,
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(decls : &'a [DeclInfo]) -> Self {
+        TypeChecker {
+            decls,
+            functions : HashMap::new(),
+            function : None,
+            diagnostics : Vec::new(),
+        }
+    }
+
+    pub fn bool_expr(&mut self, e : &BoolExpr) -> Option<Ty> {
+        match e.kind {
+            BoolExprKind::Or(ref a, ref b) | BoolExprKind::And(ref a, ref b) => {
+                let ta = self.bool_expr(a);
+                let tb = self.bool_expr(b);
+                let ok_a = self.expect(Ty::Bool, ta, a.span);
+                let ok_b = self.expect(Ty::Bool, tb, b.span);
+                if ok_a && ok_b { Some(Ty::Bool) } else { None }
+            },
+            BoolExprKind::Eq(ref a, ref b) | BoolExprKind::Neq(ref a, ref b) => {
+                let (ta, tb) = (self.bool_expr(a)?, self.bool_expr(b)?);
+                // Numbers are compared with numbers and booleans with booleans.
+                if ta.is_numeric() != tb.is_numeric() {
+                    let msg = format!("cannot compare `{}` with `{}`", ta, tb);
+                    let diag = Diagnostic::error(&msg, e.span)
+                        .with_code(E_MISMATCHED_TYPES)
+                        .with_primary_label("operands of different types")
+                        .with_label(a.span, &format!("`{}`", ta))
+                        .with_label(b.span, &format!("`{}`", tb));
+                    self.diagnostics.push(diag);
+                    return None;
+                }
+                Some(Ty::Bool)
+            },
+            BoolExprKind::Relop(_, ref a, ref b) => {
+                let ta = self.num_expr(a);
+                let tb = self.num_expr(b);
+                let ok_a = self.numeric("comparison", ta, a.span);
+                let ok_b = self.numeric("comparison", tb, b.span);
+                if ok_a && ok_b { Some(Ty::Bool) } else { None }
+            },
+            BoolExprKind::NumExpr(ref a) => self.num_expr(a),
+        }
+    }
+
+    pub fn num_expr(&mut self, e : &NumExpr) -> Option<Ty> {
+        match e.kind {
+            NumExprKind::Add(ref a, ref b) | NumExprKind::Sub(ref a, ref b) |
+            NumExprKind::Mul(ref a, ref b) | NumExprKind::Div(ref a, ref b) => {
+                let ta = self.num_expr(a);
+                let tb = self.num_expr(b);
+                let ok_a = self.numeric("arithmetic", ta, a.span);
+                let ok_b = self.numeric("arithmetic", tb, b.span);
+                if !ok_a || !ok_b {
+                    None
+                } else if ta == Some(Ty::Float) || tb == Some(Ty::Float) {
+                    Some(Ty::Float)
+                } else {
+                    Some(Ty::Int)
+                }
+            },
+            NumExprKind::Minus(ref a) => {
+                let ta = self.num_expr(a);
+                if self.numeric("arithmetic", ta, a.span) { ta } else { None }
+            },
+            NumExprKind::Not(ref a) => {
+                let ta = self.num_expr(a);
+                if self.expect(Ty::Bool, ta, a.span) { ta } else { None }
+            },
+            NumExprKind::Expr(ref b) => self.bool_expr(b),
+            NumExprKind::Loc(ref l) => self.loc(l),
+            NumExprKind::Call(ref name, ref args) => {
+                match self.call(name, args, e.span)? {
+                    Some(t) => Some(t),
+                    None => {
+                        let msg = format!("`{}` does not return a value", name);
+                        self.diagnostics.push(Diagnostic::error(&msg, e.span)
+                            .with_code(E_NO_VALUE)
+                            .with_primary_label("used as a value here"));
+                        None
+                    },
+                }
+            },
+            NumExprKind::Num(_) => Some(Ty::Int),
+            NumExprKind::Real(_) => Some(Ty::Float),
+            NumExprKind::True | NumExprKind::False => Some(Ty::Bool),
+        }
+    }
+
+    // Arrays must be indexed once for each dimension, with integers.
+    pub fn loc(&mut self, l : &Loc) -> Option<Ty> {
+        let (name, indices) = match l.kind {
+            LocKind::Ide(ref s) => (s, &[][..]),
+            LocKind::Index(ref s, ref v) => (s, &v[..]),
+        };
+        let mut ok = true;
+        for e in indices {
+            let t = self.bool_expr(e);
+            if let Some(t) = t {
+                if t != Ty::Int {
+                    let msg = format!("mismatched types: expected `int`, found `{}`", t);
+                    self.diagnostics.push(Diagnostic::error(&msg, e.span)
+                        .with_code(E_MISMATCHED_TYPES)
+                        .with_primary_label("array indices must be `int`"));
+                    ok = false;
+                }
+            }
+        }
+        let decl = &self.decls[l.decl?.0];
+        let dims = decl.type_id.dims().len();
+        if dims != indices.len() {
+            let msg = format!("`{}` has {} dimensions but is indexed with {}", name, dims, indices.len());
+            self.diagnostics.push(Diagnostic::error(&msg, l.span)
+                .with_code(E_ARRAY_DIM)
+                .with_primary_label("wrong number of indices")
+                .with_label(decl.span, "declared here"));
+            return None;
+        }
+        if ok { Some(Ty::of(decl.type_id.basic_type)) } else { None }
+    }
+
+    // Arguments are checked against the parameters. Returns the type of the
+    // value returned, or None if the call has an error.
+    fn call(&mut self, name : &str, args : &[BoolExpr], span : Span) -> Option<Option<Ty>> {
+        let types : Vec<Option<Ty>> = args.iter().map(|e| self.bool_expr(e)).collect();
+        let (params, ret, def) = {
+            let f = self.functions.get(name)?;
+            (f.params.clone(), f.ret, f.span)
+        };
+        if params.len() != args.len() {
+            let count = |n| if n == 1 { String::from("1 argument") } else { format!("{} arguments", n) };
+            let were = if args.len() == 1 { "was" } else { "were" };
+            let msg = format!("`{}` takes {} but {} {} supplied", name, count(params.len()), args.len(), were);
+            self.diagnostics.push(Diagnostic::error(&msg, span)
+                .with_code(E_ARG_COUNT)
+                .with_primary_label(&format!("expected {}", count(params.len())))
+                .with_label(def, "defined here"));
+            return None;
+        }
+        let mut ok = true;
+        for ((p, t), e) in params.into_iter().zip(types).zip(args) {
+            ok &= self.expect(p, t, e.span);
+        }
+        if ok { Some(ret) } else { None }
+    }
+
+    fn return_value(&mut self, e : &Option<Box<BoolExpr>>, span : Span) {
+        let (name, ret) = match self.function {
+            Some((ref name, ret)) => (name.clone(), ret),
+            None => return,
+        };
+        match (ret, e.as_ref()) {
+            (Some(t), Some(e)) => {
+                let found = self.bool_expr(e);
+                self.expect(t, found, e.span);
+            },
+            (Some(t), None) => {
+                let msg = format!("`return` without a value in `{}`", name);
+                self.diagnostics.push(Diagnostic::error(&msg, span)
+                    .with_code(E_MISSING_RETURN)
+                    .with_primary_label(&format!("expected a value of type `{}`", t)));
+            },
+            (None, Some(e)) => {
+                let msg = format!("`{}` does not return a value", name);
+                self.diagnostics.push(Diagnostic::error(&msg, e.span)
+                    .with_code(E_NO_VALUE)
+                    .with_primary_label("returning a value from a `void` function"));
+            },
+            (None, None) => (),
+        }
+    }
+
+    // Report a value that cannot be used where the expected type is.
+    fn expect(&mut self, expected : Ty, found : Option<Ty>, span : Span) -> bool {
+        match found {
+            Some(t) if !expected.accepts(t) => {
+                self.diagnostics.push(mismatched(expected, t, span));
+                false
+            },
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    // Report a boolean operand of a numeric operator.
+    fn numeric(&mut self, what : &str, found : Option<Ty>, span : Span) -> bool {
+        match found {
+            Some(t) if !t.is_numeric() => {
+                let msg = format!("{} on a value of type `{}`", what, t);
+                self.diagnostics.push(Diagnostic::error(&msg, span)
+                    .with_code(E_NOT_NUMERIC)
+                    .with_primary_label("expected `int` or `float`"));
+                false
+            },
+            Some(_) => true,
+            None => false,
+        }
+    }
+}
+
+impl<'a> Visitor for TypeChecker<'a> {
+    fn visit_program(&mut self, p : &Program) {
+        for f in p.functions() {
+            self.functions.insert(f.name.clone(), Signature {
+                params : f.params.iter().map(|d| Ty::of(d.type_id.basic_type)).collect(),
+                ret    : f.ret.map(Ty::of),
+                span   : f.span,
+            });
+        }
+        for item in &p.items {
+            if let Item::Const(ref c) = *item {
+                let t = Ty::of(c.basic_type);
+                let value = self.bool_expr(&c.value);
+                if let Some(value) = value.filter(|&v| !t.accepts(v)) {
+                    let diag = mismatched(t, value, c.value.span)
+                        .with_label(c.span, &format!("`{}` has type `{}`", c.name, t));
+                    self.diagnostics.push(diag);
+                }
+            }
+        }
+        walk_program(self, p);
+    }
+
+    fn visit_function(&mut self, f : &Function) {
+        let ret = f.ret.map(Ty::of);
+        self.function = Some((f.name.clone(), ret));
+        walk_function(self, f);
+        self.function = None;
+        if let Some(t) = ret {
+            if !f.body.stmts.iter().any(|s| returns(s)) {
+                let msg = format!("function `{}` may end without returning a value", f.name);
+                self.diagnostics.push(Diagnostic::error(&msg, f.span)
+                    .with_code(E_MISSING_RETURN)
+                    .with_primary_label(&format!("expected `{}` on every path", t)));
+            }
+        }
+    }
+
+    fn visit_statement(&mut self, s : &Statement) {
+        match s.kind {
+            StatementKind::Assign(ref l, ref e) => {
+                let target = self.loc(l);
+                let value = self.bool_expr(e);
+                if let (Some(target), Some(value)) = (target, value) {
+                    if !target.accepts(value) {
+                        let diag = mismatched(target, value, e.span)
+                            .with_label(l.span, &format!("has type `{}`", target));
+                        self.diagnostics.push(diag);
+                    }
+                }
+            },
+            // Conditions are not numbers.
+            StatementKind::If(ref e, _) | StatementKind::IfElse(ref e, _, _) |
+            StatementKind::While(ref e, _) | StatementKind::DoWhile(_, ref e) => {
+                let t = self.bool_expr(e);
+                self.expect(Ty::Bool, t, e.span);
+            },
+            StatementKind::For(ref f) => {
+                if let Some(ref e) = f.cond {
+                    let t = self.bool_expr(e);
+                    self.expect(Ty::Bool, t, e.span);
+                }
+            },
+            // Switches are on integers or booleans, with cases of the same type.
+            StatementKind::Switch(ref e, ref cases) => {
+                let t = self.bool_expr(e);
+                if t == Some(Ty::Float) {
+                    self.diagnostics.push(mismatched(Ty::Int, Ty::Float, e.span));
+                } else if let Some(t) = t {
+                    for c in cases {
+                        let found = match c.label {
+                            CaseLabel::Int(_) => Ty::Int,
+                            CaseLabel::Bool(_) => Ty::Bool,
+                            CaseLabel::Default => continue,
+                        };
+                        if found != t {
+                            let diag = mismatched(t, found, c.span)
+                                .with_label(e.span, &format!("this has type `{}`", t));
+                            self.diagnostics.push(diag);
+                        }
+                    }
+                }
+            },
+            StatementKind::Return(ref e) => {
+                self.return_value(e, s.span);
+                return;
+            },
+            StatementKind::Call(ref name, ref args) => {
+                self.call(name, args, s.span);
+                return;
+            },
+            StatementKind::Labeled(..) | StatementKind::Break(_) | StatementKind::Continue(_) |
+            StatementKind::BlockStmt(_) | StatementKind::Error => (),
+        }
+        walk_statement(self, s);
+    }
+}
+
+// True if every path through the statement ends with a return. Loops are
+// not followed, even those that never end.
+fn returns(s : &Statement) -> bool {
+    match s.kind {
+        StatementKind::Return(_) => true,
+        StatementKind::BlockStmt(ref b) => b.stmts.iter().any(|s| returns(s)),
+        StatementKind::IfElse(_, ref s1, ref s2) => returns(s1) && returns(s2),
+        StatementKind::DoWhile(ref s1, _) | StatementKind::Labeled(_, ref s1) => returns(s1),
+        StatementKind::Switch(_, ref cases) => {
+            covers_all(cases) && cases.iter().all(|c| c.stmts.iter().any(|s| returns(s)))
+        },
+        _ => false,
+    }
+}
+
+pub fn mismatched(expected : Ty, found : Ty, span : Span) -> Diagnostic {
+    let msg = format!("mismatched types: expected `{}`, found `{}`", expected, found);
+    Diagnostic::error(&msg, span)
+        .with_code(E_MISMATCHED_TYPES)
+        .with_primary_label(&format!("expected `{}`", expected))
+}
@@ -0,0 +1,228 @@
+/*
+    Definite assignment: finds the reads of variables that may not have been
+    assigned yet on some path from their declaration. The set of variables
+    surely assigned flows through the statements: both branches of an if
+    must assign a variable for it to be assigned after it, the body of a
+    while or a for may not run at all, the body of a do runs at least once,
+    break and continue carry what is assigned to where they jump. Parameters
+    are assigned by the call, globals and constants before the program starts.
+    The elements of arrays are not tracked, so arrays are never reported.
+    Reports are warnings of the uninitialized lint.
+*/
+use std::collections::HashSet;
+use ast::statement::{Program, Block, Statement, StatementKind, DeclId, covers_all};
+use ast::expression::{BoolExpr, Loc, LocKind};
+use ast::visit::{Visitor, walk_loc};
+use resolver::{DeclInfo, DeclKind};
+use lexer::Span;
+use diagnostics::Diagnostic;
+use lint::UNINITIALIZED;
+
+// Variables surely assigned at a point of the program, None where the point
+// cannot be reached, after a break.
+type Assigned = Option<HashSet<DeclId>>;
+
+// What is assigned where the breaks and the continues of a loop go.
+struct LoopInit {
+    label     : Option<String>,
+    breaks    : Assigned,
+    continues : Assigned,
+}
+
+pub fn check(program : &Program, decls : &[DeclInfo]) -> Vec<Diagnostic> {
+    let mut c = InitChecker {
+        decls,
+        loops : Vec::new(),
+        diagnostics : Vec::new(),
+    };
+    let assigned : HashSet<DeclId> = (0..decls.len())
+        .filter(|&i| decls[i].kind != DeclKind::Local)
+        .map(DeclId)
+        .collect();
+    for f in program.functions() {
+        c.block(&f.body, Some(assigned.clone()));
+    }
+    if let Some(ref b) = program.block {
+        c.block(b, Some(assigned));
+    }
+    c.diagnostics
+}
+
+struct InitChecker<'a> {
+    decls       : &'a [DeclInfo],
+    loops       : Vec<LoopInit>,
+    diagnostics : Vec<Diagnostic>,
+}
+
+// Meet of two paths: assigned on both.
+fn meet(a : Assigned, b : Assigned) -> Assigned {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.intersection(&b).cloned().collect()),
+        (Some(a), None) => Some(a),
+        (None, b) => b,
+    }
+}
+
+impl<'a> InitChecker<'a> {
+    fn block(&mut self, b : &Block, mut state : Assigned) -> Assigned {
+        for s in &b.stmts {
+            state = self.statement(s, state);
+        }
+        state
+    }
+
+    fn statement(&mut self, s : &Statement, mut state : Assigned) -> Assigned {
+        match s.kind {
+            StatementKind::Assign(ref l, ref e) => {
+                if let LocKind::Index(_, ref indices) = l.kind {
+                    for i in indices {
+                        self.reads(i, &mut state);
+                    }
+                }
+                self.reads(e, &mut state);
+                if let (Some(ref mut set), Some(id)) = (state.as_mut(), l.decl) {
+                    set.insert(id);
+                }
+                state
+            },
+            StatementKind::If(ref e, ref s1) => {
+                self.reads(e, &mut state);
+                let after = self.statement(s1, state.clone());
+                meet(state, after)
+            },
+            StatementKind::IfElse(ref e, ref s1, ref s2) => {
+                self.reads(e, &mut state);
+                let after1 = self.statement(s1, state.clone());
+                let after2 = self.statement(s2, state);
+                meet(after1, after2)
+            },
+            StatementKind::While(..) | StatementKind::For(_) | StatementKind::DoWhile(..) => {
+                self.looping(s, None, state)
+            },
+            // Without a default, the switch may run no case.
+            StatementKind::Switch(ref e, ref cases) => {
+                self.reads(e, &mut state);
+                let mut after = if covers_all(cases) { None } else { state.clone() };
+                for c in cases {
+                    let mut end = state.clone();
+                    for s in &c.stmts {
+                        end = self.statement(s, end);
+                    }
+                    after = meet(after, end);
+                }
+                after
+            },
+            StatementKind::Labeled(ref name, ref s1) => self.looping(s1, Some(name.clone()), state),
+            StatementKind::Break(ref label) => {
+                let target = self.target(label);
+                target.breaks = meet(target.breaks.take(), state);
+                None
+            },
+            StatementKind::Continue(ref label) => {
+                let target = self.target(label);
+                target.continues = meet(target.continues.take(), state);
+                None
+            },
+            StatementKind::Return(ref e) => {
+                if let Some(ref e) = *e {
+                    self.reads(e, &mut state);
+                }
+                None
+            },
+            StatementKind::Call(_, ref args) => {
+                for e in args {
+                    self.reads(e, &mut state);
+                }
+                state
+            },
+            StatementKind::BlockStmt(ref b) => self.block(b, state),
+            StatementKind::Error => state,
+        }
+    }
+
+    // Assignments only add to the set, so what is assigned before the first
+    // iteration is all that is assigned at the start of each one: the body
+    // is analyzed once.
+    fn looping(&mut self, s : &Statement, label : Option<String>, mut state : Assigned) -> Assigned {
+        self.loops.push(LoopInit { label, breaks : None, continues : None });
+        let after = match s.kind {
+            StatementKind::While(ref e, ref s1) => {
+                self.reads(e, &mut state);
+                self.statement(s1, state.clone());
+                Some(state)
+            },
+            StatementKind::For(ref f) => {
+                if let Some(ref init) = f.init {
+                    state = self.statement(init, state);
+                }
+                if let Some(ref e) = f.cond {
+                    self.reads(e, &mut state);
+                }
+                let end = self.statement(&f.body, state.clone());
+                let end = meet(end, self.loops.last_mut().unwrap().continues.take());
+                if let Some(ref step) = f.step {
+                    self.statement(step, end);
+                }
+                // Without a condition, only a break leaves the loop.
+                if f.cond.is_some() { Some(state) } else { None }
+            },
+            StatementKind::DoWhile(ref s1, ref e) => {
+                let end = self.statement(s1, state);
+                let mut end = meet(end, self.loops.last_mut().unwrap().continues.take());
+                self.reads(e, &mut end);
+                Some(end)
+            },
+            _ => unreachable!("Label on a statement that is not a loop."),
+        };
+        let l = self.loops.pop().unwrap();
+        match after {
+            Some(after) => meet(after, l.breaks),
+            None => l.breaks,
+        }
+    }
+
+    // The loop a break or continue goes to; the resolver checked it exists.
+    fn target(&mut self, label : &Option<String>) -> &mut LoopInit {
+        self.loops.iter_mut().rev()
+            .find(|l| label.is_none() || l.label == *label)
+            .unwrap()
+    }
+
+    // Report the variables read by the expression that may not be assigned.
+    // Each one is reported once, then it counts as assigned.
+    fn reads(&mut self, e : &BoolExpr, state : &mut Assigned) {
+        let set = match *state {
+            Some(ref mut set) => set,
+            None => return,
+        };
+        let mut reads = Reads(Vec::new());
+        reads.visit_bool_expr(e);
+        for (decl, span) in reads.0 {
+            let id = match decl {
+                Some(id) => id,
+                None => continue,
+            };
+            let decl = &self.decls[id.0];
+            if set.contains(&id) || !decl.type_id.dim_width.is_empty() {
+                continue;
+            }
+            let msg = format!("`{}` may be used before it is assigned", decl.name);
+            self.diagnostics.push(Diagnostic::warning(&msg, span)
+                .with_code(UNINITIALIZED.name)
+                .with_primary_label("read here")
+                .with_label(decl.span, "declared here without a value"));
+            set.insert(id);
+        }
+    }
+}
+
+// Declaration and span of the locations read by an expression, indices
+// included.
+struct Reads(Vec<(Option<DeclId>, Span)>);
+
+impl Visitor for Reads {
+    fn visit_loc(&mut self, l : &Loc) {
+        self.0.push((l.decl, l.span));
+        walk_loc(self, l);
+    }
+}